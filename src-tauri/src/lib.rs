@@ -1,310 +1,6545 @@
-use rusqlite::{params, Connection, Result};
+use rusqlite::{params, Connection, OptionalExtension, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
-use std::sync::Mutex;
-use tauri::{AppHandle, Manager, State};
+use std::path::{Path, PathBuf};
+use r2d2_sqlite::SqliteConnectionManager;
+use std::sync::{Mutex, MutexGuard, RwLock};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct InventoryItem {
     pub id: Option<i64>,
     pub name: String,
     pub image_path: Option<String>,
-    pub cantidad_necesaria: i32,
-    pub cantidad_disponible: i32,
+    pub cantidad_necesaria: f64,
+    pub cantidad_disponible: f64,
     pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+    pub category: Option<String>,
+    pub thumbnail_path: Option<String>,
+    pub deleted_at: Option<String>,
+    pub precio_unitario: Option<f64>,
+    pub costo_unitario: Option<f64>,
+    pub supplier_id: Option<i64>,
+    pub descripcion: Option<String>,
+    pub codigo_barras: Option<String>,
+    pub unidad: Option<String>,
+    // Respaldos anteriores a esta columna no la traen
+    #[serde(default)]
+    pub version: i64,
+    pub punto_reorden: Option<f64>,
+    #[serde(default)]
+    pub archived: bool,
+    pub capacidad_maxima: Option<f64>,
+    // Tipo de la imagen tal como llegó; puede haberse guardado convertida
+    pub image_source_mime: Option<String>,
 }
 
-pub struct AppState {
-    db: Mutex<Connection>,
-    app_handle: AppHandle,
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Supplier {
+    pub id: Option<i64>,
+    pub name: String,
+    pub contact: Option<String>,
+    pub phone: Option<String>,
 }
 
-fn get_app_data_dir(app_handle: &AppHandle) -> PathBuf {
-    app_handle
-        .path()
-        .app_data_dir()
-        .expect("Failed to get app data directory")
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Tag {
+    pub id: i64,
+    pub name: String,
 }
 
-fn init_database(app_handle: &AppHandle) -> Result<Connection> {
-    let mut db_path = get_app_data_dir(app_handle);
-    fs::create_dir_all(&db_path).expect("Failed to create app data directory");
-    db_path.push("inventario.db");
+// Filtros del listado; los mismos sirven para exportar exactamente lo que se ve
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ItemFilter {
+    // Texto contenido en el nombre, sin distinguir mayúsculas ni acentos
+    pub query: Option<String>,
+    pub category: Option<String>,
+    #[serde(default)]
+    pub low_stock_only: bool,
+    #[serde(default)]
+    pub include_archived: bool,
+    // Columnas de sort_clause; sin valor se ordena por fecha de creación descendente
+    pub sort_by: Option<String>,
+    #[serde(default)]
+    pub ascending: bool,
+}
 
-    let conn = Connection::open(db_path)?;
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PaginatedItems {
+    pub items: Vec<InventoryItem>,
+    pub total: i64,
+}
 
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS inventory (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            name TEXT NOT NULL,
-            image_path TEXT,
-            cantidad_necesaria INTEGER NOT NULL DEFAULT 0,
-            cantidad_disponible INTEGER NOT NULL DEFAULT 0,
-            created_at DATETIME DEFAULT (datetime('now', 'localtime'))
-        )",
-        [],
-    )?;
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StockMovement {
+    pub id: i64,
+    pub item_id: i64,
+    pub delta: f64,
+    pub reason: Option<String>,
+    pub previous_qty: f64,
+    pub new_qty: f64,
+    pub timestamp: String,
+}
 
-    // Agregar columnas si la tabla ya existe pero no tiene estos campos
-    let _ = conn.execute("ALTER TABLE inventory ADD COLUMN cantidad_necesaria INTEGER NOT NULL DEFAULT 0", []);
-    let _ = conn.execute("ALTER TABLE inventory ADD COLUMN cantidad_disponible INTEGER NOT NULL DEFAULT 0", []);
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImportRowError {
+    pub row: usize,
+    pub message: String,
+}
 
-    Ok(conn)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImportReport {
+    pub inserted: usize,
+    pub skipped: usize,
+    pub errors: Vec<ImportRowError>,
+    pub dry_run: bool,
 }
 
-#[tauri::command]
-fn get_all_items(state: State<AppState>) -> Result<Vec<InventoryItem>, String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
-    let mut stmt = db
-        .prepare("SELECT id, name, image_path, cantidad_necesaria, cantidad_disponible, created_at FROM inventory ORDER BY created_at DESC")
-        .map_err(|e| e.to_string())?;
+// snake_case como el resto de los campos que recibe el frontend
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Stats {
+    pub total_items: i64,
+    pub total_available: f64,
+    pub total_needed: f64,
+    pub low_stock_count: i64,
+    pub out_of_stock_count: i64,
+}
 
-    let items = stmt
-        .query_map([], |row| {
-            Ok(InventoryItem {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                image_path: row.get(2)?,
-                cantidad_necesaria: row.get(3)?,
-                cantidad_disponible: row.get(4)?,
-                created_at: row.get(5)?,
-            })
-        })
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())?;
+// Conteo físico (inventario anual); solo puede haber uno abierto a la vez
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CountSession {
+    pub id: i64,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub zeroed: bool,
+}
 
-    Ok(items)
+// variance = final_qty - snapshot_qty; final_qty y variance se llenan al
+// terminar el conteo
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CountLine {
+    pub item_id: i64,
+    pub item_name: String,
+    pub snapshot_qty: f64,
+    pub counted_qty: Option<f64>,
+    pub final_qty: Option<f64>,
+    pub variance: Option<f64>,
 }
 
-#[tauri::command]
-fn add_item(
-    name: String,
-    image_base64: Option<String>,
-    cantidad_necesaria: i32,
-    cantidad_disponible: i32,
-    state: State<AppState>
-) -> Result<InventoryItem, String> {
-    let mut image_path = None;
+// fill_percent es None cuando el artículo no tiene cantidad necesaria
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VarianceLine {
+    pub id: i64,
+    pub name: String,
+    pub needed: f64,
+    pub available: f64,
+    pub fill_percent: Option<f64>,
+}
 
-    if let Some(base64_data) = image_base64 {
-        image_path = Some(save_image(&base64_data, &state.app_handle)?);
-    }
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShortfallLine {
+    pub id: i64,
+    pub name: String,
+    pub needed: f64,
+    pub available: f64,
+    pub shortfall: f64,
+}
 
-    // Obtener fecha y hora local
-    let local_time = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DashboardData {
+    pub stats: Stats,
+    pub low_stock_items: Vec<InventoryItem>,
+    pub recent_movements: Vec<StockMovement>,
+}
 
-    let db = state.db.lock().map_err(|e| e.to_string())?;
-    db.execute(
-        "INSERT INTO inventory (name, image_path, cantidad_necesaria, cantidad_disponible, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
-        params![name, image_path, cantidad_necesaria, cantidad_disponible, local_time],
-    )
-    .map_err(|e| e.to_string())?;
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ValuationReport {
+    pub total_cost_value: f64,
+    pub total_retail_value: f64,
+}
 
-    let id = db.last_insert_rowid();
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CleanupReport {
+    pub deleted_files: usize,
+    pub bytes_freed: u64,
+    pub files: Vec<String>,
+    pub dry_run: bool,
+}
 
-    let mut stmt = db
-        .prepare("SELECT id, name, image_path, cantidad_necesaria, cantidad_disponible, created_at FROM inventory WHERE id = ?1")
-        .map_err(|e| e.to_string())?;
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RepairReport {
+    pub cleared_images: usize,
+    pub cleared_thumbnails: usize,
+    pub orphaned_files: usize,
+    pub orphaned_bytes: u64,
+    pub orphans: Vec<String>,
+}
 
-    let item = stmt
-        .query_row([id], |row| {
-            Ok(InventoryItem {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                image_path: row.get(2)?,
-                cantidad_necesaria: row.get(3)?,
-                cantidad_disponible: row.get(4)?,
-                created_at: row.get(5)?,
-            })
-        })
-        .map_err(|e| e.to_string())?;
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImageOwner {
+    pub item_id: i64,
+    pub item_name: String,
+}
 
-    Ok(item)
+// Un archivo de la carpeta de imágenes; items queda vacío si ningún artículo
+// lo usa y tiene varios cuando la misma foto se comparte
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImageInfo {
+    pub filename: String,
+    pub path: String,
+    pub size_bytes: u64,
+    pub is_thumbnail: bool,
+    pub items: Vec<ImageOwner>,
 }
 
-#[tauri::command]
-fn update_item(
-    id: i64,
-    name: String,
-    image_base64: Option<String>,
-    cantidad_necesaria: i32,
-    cantidad_disponible: i32,
-    state: State<AppState>,
-) -> Result<InventoryItem, String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
+// Espacio usado por la aplicación; las imágenes incluyen las miniaturas
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DbStats {
+    pub file_size_bytes: u64,
+    pub page_count: i64,
+    pub item_count: i64,
+    pub image_count: usize,
+    pub images_size_bytes: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MissingImage {
+    pub item_id: i64,
+    pub name: String,
+    pub image_path: String,
+}
 
-    let mut image_path: Option<String> = None;
+// Estado del directorio de datos; problems explica en español lo que falló
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StorageStatus {
+    pub data_dir: String,
+    pub exists: bool,
+    pub writable: bool,
+    pub images_dir_ok: bool,
+    pub free_space_bytes: Option<u64>,
+    pub problems: Vec<String>,
+}
 
-    if let Some(base64_data) = image_base64 {
-        // Eliminar imagen anterior si existe
-        let mut stmt = db
-            .prepare("SELECT image_path FROM inventory WHERE id = ?1")
-            .map_err(|e| e.to_string())?;
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImportImageReport {
+    pub matched: usize,
+    pub unmatched: usize,
+    pub unmatched_files: Vec<String>,
+    // Archivos que coincidieron pero no se pudieron guardar, con el motivo
+    pub failed_files: Vec<String>,
+}
 
-        if let Ok(old_path) = stmt.query_row([id], |row| row.get::<_, Option<String>>(0)) {
-            if let Some(path) = old_path {
-                let _ = fs::remove_file(&path);
-            }
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImageRelocationReport {
+    pub relocated: usize,
+    pub missing: usize,
+}
+
+// ok se refiere a la base de datos; las imágenes faltantes se informan aparte
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IntegrityReport {
+    pub ok: bool,
+    pub integrity_errors: Vec<String>,
+    pub foreign_key_errors: Vec<String>,
+    pub missing_images: Vec<MissingImage>,
+}
+
+// Artículos cuyo nombre coincide al ignorar mayúsculas, acentos y espacios
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DuplicateGroup {
+    pub normalized_name: String,
+    pub items: Vec<InventoryItem>,
+}
+
+// Artículos de una categoría para la vista de árbol; los que no tienen
+// categoría van en el grupo UNCATEGORIZED_LABEL
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CategoryGroup {
+    pub category: String,
+    pub count: usize,
+    pub items: Vec<InventoryItem>,
+}
+
+// Línea de compra sugerida; shortfall es lo que falta para llegar a lo necesario
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReorderLine {
+    pub item_id: i64,
+    pub name: String,
+    pub cantidad_disponible: f64,
+    pub cantidad_necesaria: f64,
+    pub shortfall: f64,
+    pub supplier_id: Option<i64>,
+    pub supplier_name: Option<String>,
+}
+
+// Carga del evento "inventory-changed"; operation es "add", "update",
+// "adjust", "archive", "delete", "hard_delete" o "restore"
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct InventoryChanged {
+    pub id: i64,
+    pub operation: String,
+}
+
+// Datos de un artículo nuevo; usado por add_item y por la carga en lote
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NewItem {
+    pub name: String,
+    pub image_base64: Option<String>,
+    pub cantidad_necesaria: f64,
+    pub cantidad_disponible: f64,
+    pub category: Option<String>,
+    pub precio_unitario: Option<f64>,
+    pub costo_unitario: Option<f64>,
+    pub supplier_id: Option<i64>,
+    pub descripcion: Option<String>,
+    pub codigo_barras: Option<String>,
+    pub unidad: Option<String>,
+    pub punto_reorden: Option<f64>,
+    pub capacidad_maxima: Option<f64>,
+    // Fecha original al importar datos históricos; sin ella se usa la actual
+    pub created_at: Option<String>,
+    pub reason: Option<String>,
+    #[serde(default)]
+    pub allow_duplicate: bool,
+}
+
+// Artículo dentro de un respaldo JSON, con la imagen incrustada
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BackupItem {
+    #[serde(flatten)]
+    pub item: InventoryItem,
+    pub image_base64: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct JsonBackup {
+    pub exported_at: String,
+    pub items: Vec<BackupItem>,
+}
+
+#[derive(Debug)]
+pub enum AppError {
+    Database(String),
+    NotFound(i64),
+    ImageDecode(String),
+    Io(String),
+    InvalidInput(String),
+    DuplicateName(String),
+    DuplicateBarcode(String),
+    VersionConflict(i64),
+    SupplierNotFound(i64),
+    TagNotFound(i64),
+    ImageTooLarge(usize),
+    InvalidImageData(String),
+    LockPoisoned,
+    DatabaseBusy,
+}
+
+impl AppError {
+    // Código estable para que el frontend pueda distinguir los errores
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Database(_) => "DATABASE_ERROR",
+            AppError::NotFound(_) => "NOT_FOUND",
+            AppError::ImageDecode(_) => "IMAGE_DECODE_ERROR",
+            AppError::Io(_) => "IO_ERROR",
+            AppError::InvalidInput(_) => "INVALID_INPUT",
+            AppError::DuplicateName(_) => "ITEM_NAME_EXISTS",
+            AppError::DuplicateBarcode(_) => "BARCODE_EXISTS",
+            AppError::VersionConflict(_) => "VERSION_CONFLICT",
+            AppError::SupplierNotFound(_) => "SUPPLIER_NOT_FOUND",
+            AppError::TagNotFound(_) => "TAG_NOT_FOUND",
+            AppError::ImageTooLarge(_) => "IMAGE_TOO_LARGE",
+            AppError::InvalidImageData(_) => "INVALID_IMAGE_DATA",
+            AppError::LockPoisoned => "LOCK_POISONED",
+            AppError::DatabaseBusy => "DATABASE_BUSY",
         }
+    }
+}
 
-        image_path = Some(save_image(&base64_data, &state.app_handle)?);
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AppError::Database(msg) => write!(f, "Error de base de datos: {}", msg),
+            AppError::NotFound(id) => write!(f, "No se encontró el artículo con id {}", id),
+            AppError::ImageDecode(msg) => write!(f, "Imagen inválida: {}", msg),
+            AppError::Io(msg) => write!(f, "Error de archivo: {}", msg),
+            AppError::InvalidInput(msg) => write!(f, "{}", msg),
+            AppError::DuplicateName(name) => write!(f, "Ya existe un artículo llamado \"{}\"", name),
+            AppError::VersionConflict(id) => write!(
+                f,
+                "El artículo con id {} fue modificado desde otro lugar; recarga los datos antes de guardar",
+                id
+            ),
+            AppError::DuplicateBarcode(code) => write!(f, "Ya existe un artículo con el código de barras \"{}\"", code),
+            AppError::SupplierNotFound(id) => write!(f, "No se encontró el proveedor con id {}", id),
+            AppError::TagNotFound(id) => write!(f, "No se encontró la etiqueta con id {}", id),
+            AppError::ImageTooLarge(max) => write!(f, "La imagen supera el tamaño máximo de {} MB", max / (1024 * 1024)),
+            AppError::InvalidImageData(msg) => write!(f, "Los datos de la imagen no son base64 válido: {}", msg),
+            AppError::LockPoisoned => write!(f, "La base de datos quedó bloqueada por un error anterior"),
+            AppError::DatabaseBusy => write!(
+                f,
+                "La base de datos está ocupada por otro proceso; espera un momento e intenta de nuevo"
+            ),
+        }
     }
+}
 
-    if image_path.is_some() {
-        db.execute(
-            "UPDATE inventory SET name = ?1, image_path = ?2, cantidad_necesaria = ?3, cantidad_disponible = ?4 WHERE id = ?5",
-            params![name, image_path, cantidad_necesaria, cantidad_disponible, id],
-        )
-        .map_err(|e| e.to_string())?;
-    } else {
-        db.execute(
-            "UPDATE inventory SET name = ?1, cantidad_necesaria = ?2, cantidad_disponible = ?3 WHERE id = ?4",
-            params![name, cantidad_necesaria, cantidad_disponible, id],
-        )
-        .map_err(|e| e.to_string())?;
+impl std::error::Error for AppError {}
+
+impl Serialize for AppError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("AppError", 2)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
     }
+}
 
-    let mut stmt = db
-        .prepare("SELECT id, name, image_path, cantidad_necesaria, cantidad_disponible, created_at FROM inventory WHERE id = ?1")
-        .map_err(|e| e.to_string())?;
+impl From<rusqlite::Error> for AppError {
+    fn from(e: rusqlite::Error) -> Self {
+        // Ocupada o bloqueada se distingue para poder reintentar
+        match e.sqlite_error_code() {
+            Some(rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked) => AppError::DatabaseBusy,
+            _ => AppError::Database(e.to_string()),
+        }
+    }
+}
 
-    let item = stmt
-        .query_row([id], |row| {
-            Ok(InventoryItem {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                image_path: row.get(2)?,
-                cantidad_necesaria: row.get(3)?,
-                cantidad_disponible: row.get(4)?,
-                created_at: row.get(5)?,
-            })
-        })
-        .map_err(|e| e.to_string())?;
+impl From<std::io::Error> for AppError {
+    fn from(e: std::io::Error) -> Self {
+        AppError::Io(e.to_string())
+    }
+}
 
-    Ok(item)
+impl From<base64::DecodeError> for AppError {
+    fn from(e: base64::DecodeError) -> Self {
+        AppError::InvalidImageData(e.to_string())
+    }
 }
 
-#[tauri::command]
-fn delete_item(id: i64, state: State<AppState>) -> Result<(), String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
+impl From<csv::Error> for AppError {
+    fn from(e: csv::Error) -> Self {
+        AppError::Io(e.to_string())
+    }
+}
 
-    // Eliminar imagen si existe
-    let mut stmt = db
-        .prepare("SELECT image_path FROM inventory WHERE id = ?1")
-        .map_err(|e| e.to_string())?;
+impl From<rust_xlsxwriter::XlsxError> for AppError {
+    fn from(e: rust_xlsxwriter::XlsxError) -> Self {
+        AppError::Io(e.to_string())
+    }
+}
 
-    if let Ok(image_path) = stmt.query_row([id], |row| row.get::<_, Option<String>>(0)) {
-        if let Some(path) = image_path {
-            let _ = fs::remove_file(&path);
-        }
+impl From<r2d2::Error> for AppError {
+    fn from(e: r2d2::Error) -> Self {
+        AppError::Database(e.to_string())
     }
+}
 
-    db.execute("DELETE FROM inventory WHERE id = ?1", params![id])
-        .map_err(|e| e.to_string())?;
+impl<T> From<std::sync::PoisonError<T>> for AppError {
+    fn from(_: std::sync::PoisonError<T>) -> Self {
+        AppError::LockPoisoned
+    }
+}
 
-    Ok(())
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+const THUMBNAIL_MAX_SIZE: u32 = 200;
+// Tamaño máximo por defecto de la imagen ya decodificada (preferencia max_image_bytes)
+const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+// Las imágenes más grandes se reducen a este lado máximo antes de guardarse
+const MAX_IMAGE_DIMENSION: u32 = 2048;
+
+// Nombres más largos rompen el diseño de la lista y no aportan nada
+const MAX_NAME_CHARS: usize = 200;
+
+// Todas las fechas se guardan en UTC como ISO-8601 ("2024-05-01T14:30:00Z"),
+// que además se ordena correctamente como texto. El frontend las convierte a
+// hora local al mostrarlas.
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+fn now_timestamp() -> String {
+    chrono::Utc::now().format(TIMESTAMP_FORMAT).to_string()
 }
 
-#[tauri::command]
-fn get_db_path(state: State<AppState>) -> Result<String, String> {
-    let mut db_path = get_app_data_dir(&state.app_handle);
-    db_path.push("inventario.db");
-    
-    Ok(db_path.to_string_lossy().to_string())
+// Acepta ISO-8601 con zona ("2024-05-01T14:30:00Z", "2024-05-01T08:30:00-06:00")
+// o el formato local sin zona de versiones anteriores, y lo lleva al formato
+// guardado. None si no es una fecha válida.
+fn parse_timestamp(value: &str) -> Option<String> {
+    let value = value.trim();
+    match chrono::DateTime::parse_from_rfc3339(value) {
+        Ok(date) => Some(date.with_timezone(&chrono::Utc).format(TIMESTAMP_FORMAT).to_string()),
+        Err(_) => local_timestamp_to_utc(value),
+    }
 }
 
+// Un timestamp guardado, en la hora local y con meses y días en el idioma de la
+// preferencia locale. format es "iso" o un patrón de chrono, p. ej. "%-d de %B
+// de %Y" para "15 de enero de 2025". Solo sirve para mostrar; para ordenar se
+// usa el valor guardado.
 #[tauri::command]
-fn fix_image_paths(state: State<AppState>) -> Result<i32, String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
-    
-    // Obtener la nueva ruta de imágenes
-    let mut new_images_dir = get_app_data_dir(&state.app_handle);
-    new_images_dir.push("inventory_images");
-    
-    // Obtener todos los items con imágenes
-    let mut stmt = db
-        .prepare("SELECT id, image_path FROM inventory WHERE image_path IS NOT NULL")
-        .map_err(|e| e.to_string())?;
-    
-    let items: Vec<(i64, String)> = stmt
-        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())?;
-    
-    let mut updated = 0;
-    
-    for (id, old_path) in items {
-        // Extraer solo el nombre del archivo
-        if let Some(filename) = std::path::Path::new(&old_path).file_name() {
-            let mut new_path = new_images_dir.clone();
-            new_path.push(filename);
-            
-            // Verificar si el archivo existe en la nueva ubicación
-            if new_path.exists() {
-                db.execute(
-                    "UPDATE inventory SET image_path = ?1 WHERE id = ?2",
-                    params![new_path.to_string_lossy().to_string(), id],
-                )
-                .map_err(|e| e.to_string())?;
-                updated += 1;
-            }
-        }
-    }
-    
-    Ok(updated)
+fn format_timestamp(ts: String, format: String, state: State<AppState>) -> Result<String, AppError> {
+    use chrono::format::{Item, StrftimeItems};
+    use chrono::TimeZone;
+
+    let utc = parse_timestamp(&ts)
+        .and_then(|value| chrono::NaiveDateTime::parse_from_str(&value, TIMESTAMP_FORMAT).ok())
+        .ok_or_else(|| AppError::InvalidInput(format!("Fecha inválida: {}", ts)))?;
+    let local = chrono::Utc.from_utc_datetime(&utc).with_timezone(&chrono::Local);
+
+    if format == "iso" {
+        return Ok(local.to_rfc3339_opts(chrono::SecondsFormat::Secs, false));
+    }
+
+    let locale = app_setting(&state.app_handle, "locale")
+        .and_then(|value| value.parse::<chrono::Locale>().ok())
+        .unwrap_or(chrono::Locale::es_MX);
+    let items: Vec<Item> = StrftimeItems::new_with_locale(&format, locale).collect();
+    if items.iter().any(|item| matches!(item, Item::Error)) {
+        return Err(AppError::InvalidInput(format!("Formato de fecha inválido: {}", format)));
+    }
+
+    Ok(local.format_localized_with_items(items.into_iter(), locale).to_string())
 }
 
-fn save_image(base64_data: &str, app_handle: &AppHandle) -> Result<String, String> {
-    use base64::{Engine as _, engine::general_purpose};
+// Valor de image_path para las imágenes guardadas en la tabla item_images
+const DB_IMAGE_MARKER: &str = "db:item_images";
 
-    let image_data = if base64_data.contains("base64,") {
-        let parts: Vec<&str> = base64_data.split("base64,").collect();
-        general_purpose::STANDARD.decode(parts[1]).map_err(|e| e.to_string())?
-    } else {
-        general_purpose::STANDARD.decode(base64_data).map_err(|e| e.to_string())?
-    };
+const ITEM_COLUMNS: &str =
+    "id, name, image_path, cantidad_necesaria, cantidad_disponible, created_at, updated_at, category, thumbnail_path, deleted_at, precio_unitario, costo_unitario, supplier_id, descripcion, codigo_barras, unidad, version, punto_reorden, archived, capacidad_maxima, image_source_mime";
 
-    let mut images_dir = get_app_data_dir(app_handle);
-    images_dir.push("inventory_images");
-    fs::create_dir_all(&images_dir).map_err(|e| e.to_string())?;
+fn item_from_row(row: &rusqlite::Row) -> Result<InventoryItem> {
+    Ok(InventoryItem {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        image_path: row.get(2)?,
+        cantidad_necesaria: row.get(3)?,
+        cantidad_disponible: row.get(4)?,
+        created_at: row.get(5)?,
+        updated_at: row.get(6)?,
+        category: row.get(7)?,
+        thumbnail_path: row.get(8)?,
+        deleted_at: row.get(9)?,
+        precio_unitario: row.get(10)?,
+        costo_unitario: row.get(11)?,
+        supplier_id: row.get(12)?,
+        descripcion: row.get(13)?,
+        codigo_barras: row.get(14)?,
+        unidad: row.get(15)?,
+        version: row.get(16)?,
+        punto_reorden: row.get(17)?,
+        archived: row.get(18)?,
+        capacidad_maxima: row.get(19)?,
+        image_source_mime: row.get(20)?,
+    })
+}
 
-    let filename = format!("img_{}.png", chrono::Utc::now().timestamp_millis());
-    let mut image_path = images_dir.clone();
-    image_path.push(&filename);
+type DbPool = r2d2::Pool<SqliteConnectionManager>;
+type PooledConnection = r2d2::PooledConnection<SqliteConnectionManager>;
 
-    fs::write(&image_path, image_data).map_err(|e| e.to_string())?;
+// Conexiones abiertas a la vez; las lecturas pueden ir en paralelo gracias a WAL
+const DB_POOL_SIZE: u32 = 4;
 
-    Ok(image_path.to_string_lossy().to_string())
+pub struct AppState {
+    // RwLock para poder reemplazar el pool al restaurar la base de datos
+    db: RwLock<DbPool>,
+    // Los escritores se atienden de a uno: las comprobaciones previas (nombre
+    // único, versión) y la escritura deben ser atómicas entre comandos
+    writer: Mutex<()>,
+    // Último artículo enviado a la papelera, para undo_last_delete
+    last_deleted: Mutex<Option<i64>>,
+    // Cuándo terminó la última escritura propia, para distinguirla de las
+    // ediciones hechas con otra herramienta
+    last_write: Mutex<Option<Instant>>,
+    // Se reemplaza al cambiar la ubicación de la base de datos
+    db_watcher: Mutex<Option<notify::RecommendedWatcher>>,
+    // Copia de la tabla settings, se lee al iniciar y al restaurar
+    settings: RwLock<HashMap<String, String>>,
+    // Revisión periódica del stock bajo; se detiene al cerrar la aplicación
+    low_stock_scheduler: Mutex<Option<tauri::async_runtime::JoinHandle<()>>>,
+    app_handle: AppHandle,
 }
 
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run() {
-    tauri::Builder::default()
-        .plugin(tauri_plugin_opener::init())
-        .plugin(tauri_plugin_fs::init())
-        .plugin(tauri_plugin_dialog::init())
-        .setup(|app| {
-            let app_handle = app.handle().clone();
-            let conn = init_database(&app_handle).expect("Failed to initialize database");
+// Turno de escritura; al soltarlo anota la hora de la escritura
+struct WriteGuard<'a> {
+    _guard: MutexGuard<'a, ()>,
+    last_write: &'a Mutex<Option<Instant>>,
+}
 
-            app.manage(AppState {
-                db: Mutex::new(conn),
-                app_handle,
-            });
+impl Drop for WriteGuard<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut last_write) = self.last_write.lock() {
+            *last_write = Some(Instant::now());
+        }
+    }
+}
 
-            Ok(())
+// Conexión del pool que mantiene tomado el turno de escritura. La conexión se
+// declara primero para que vuelva al pool antes de liberar el turno.
+struct WriteConnection<'a> {
+    conn: PooledConnection,
+    _guard: WriteGuard<'a>,
+}
+
+impl std::ops::Deref for WriteConnection<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        &self.conn
+    }
+}
+
+impl std::ops::DerefMut for WriteConnection<'_> {
+    fn deref_mut(&mut self) -> &mut Connection {
+        &mut self.conn
+    }
+}
+
+impl AppState {
+    fn conn(&self) -> Result<PooledConnection, AppError> {
+        Ok(self.db.read()?.get()?)
+    }
+
+    fn write_guard(&self) -> Result<WriteGuard<'_>, AppError> {
+        Ok(WriteGuard {
+            _guard: self.writer.lock()?,
+            last_write: &self.last_write,
         })
-        .invoke_handler(tauri::generate_handler![
-            get_all_items,
-            add_item,
-            update_item,
-            delete_item,
-            get_db_path,
-            fix_image_paths
-        ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
-}
\ No newline at end of file
+    }
+
+    fn write_conn(&self) -> Result<WriteConnection<'_>, AppError> {
+        let guard = self.write_guard()?;
+        Ok(WriteConnection {
+            conn: self.conn()?,
+            _guard: guard,
+        })
+    }
+
+    // Las escrituras propias tardan en llegar al vigilante; dentro de este
+    // margen los cambios del archivo se atribuyen a la aplicación
+    fn wrote_recently(&self) -> bool {
+        if self.writer.try_lock().is_err() {
+            return true;
+        }
+        match self.last_write.lock() {
+            Ok(last_write) => last_write.is_some_and(|at| at.elapsed() < DB_WATCH_QUIET_PERIOD),
+            Err(_) => true,
+        }
+    }
+}
+
+const DB_WATCH_QUIET_PERIOD: Duration = Duration::from_secs(2);
+
+// Reintentos cuando otro proceso retiene la base de datos más allá de busy_timeout
+const BUSY_RETRIES: u32 = 3;
+const BUSY_BACKOFF: Duration = Duration::from_millis(200);
+
+// Vuelve a ejecutar la escritura si SQLite sigue ocupado, esperando un poco
+// más en cada intento; al agotar los reintentos devuelve DatabaseBusy
+fn retry_on_busy<T>(mut f: impl FnMut() -> Result<T, AppError>) -> Result<T, AppError> {
+    let mut attempt = 0;
+    loop {
+        match f() {
+            Err(AppError::DatabaseBusy) if attempt < BUSY_RETRIES => {
+                attempt += 1;
+                std::thread::sleep(BUSY_BACKOFF * attempt);
+            }
+            result => return result,
+        }
+    }
+}
+
+// Vigila el archivo de la base de datos (y su WAL) y emite
+// "database-changed-externally" cuando otra herramienta lo modifica, para que
+// el frontend recargue. Los avisos seguidos se agrupan en uno.
+fn start_db_watcher(app_handle: &AppHandle, db_path: &Path) -> Result<notify::RecommendedWatcher, AppError> {
+    use notify::{EventKind, RecursiveMode, Watcher};
+
+    let watch_err = |e: notify::Error| AppError::Io(format!("No se pudo vigilar la base de datos: {}", e));
+
+    let file_name = db_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or_else(|| AppError::InvalidInput(format!("Ruta de base de datos inválida: {}", db_path.display())))?;
+    let watched_names = [file_name.clone(), format!("{}-wal", file_name)];
+    let dir = db_path.parent().unwrap_or(Path::new(".")).to_path_buf();
+
+    let app_handle = app_handle.clone();
+    let mut last_emit: Option<Instant> = None;
+
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        let Ok(event) = res else {
+            return;
+        };
+        if !matches!(event.kind, EventKind::Modify(_) | EventKind::Create(_)) {
+            return;
+        }
+        let touches_db = event.paths.iter().any(|path| {
+            path.file_name()
+                .is_some_and(|name| watched_names.iter().any(|watched| name.to_string_lossy() == *watched))
+        });
+        if !touches_db {
+            return;
+        }
+
+        let Some(state) = app_handle.try_state::<AppState>() else {
+            return;
+        };
+        if state.wrote_recently() || last_emit.is_some_and(|at| at.elapsed() < DB_WATCH_QUIET_PERIOD) {
+            return;
+        }
+
+        last_emit = Some(Instant::now());
+        if let Err(e) = app_handle.emit("database-changed-externally", ()) {
+            eprintln!("No se pudo emitir database-changed-externally: {}", e);
+        }
+    })
+    .map_err(watch_err)?;
+
+    watcher.watch(&dir, RecursiveMode::NonRecursive).map_err(watch_err)?;
+
+    Ok(watcher)
+}
+
+// Un fallo del vigilante no impide usar la aplicación, solo se registra
+fn restart_db_watcher(state: &AppState, db_path: &Path) {
+    let watcher = match start_db_watcher(&state.app_handle, db_path) {
+        Ok(watcher) => Some(watcher),
+        Err(e) => {
+            eprintln!("{}", e);
+            None
+        }
+    };
+    match state.db_watcher.lock() {
+        Ok(mut current) => *current = watcher,
+        Err(e) => eprintln!("No se pudo reemplazar el vigilante de la base de datos: {}", e),
+    }
+}
+
+fn get_app_data_dir(app_handle: &AppHandle) -> Result<PathBuf, AppError> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::Io(format!("No se pudo obtener el directorio de datos: {}", e)))
+}
+
+fn get_images_dir(app_handle: &AppHandle) -> Result<PathBuf, AppError> {
+    let mut images_dir = get_app_data_dir(app_handle)?;
+    images_dir.push("inventory_images");
+    Ok(images_dir)
+}
+
+// Preferencias locales del equipo que deben leerse antes de abrir la base de datos
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+struct LocalSettings {
+    database_path: Option<String>,
+    // Guardar las imágenes nuevas dentro de la base de datos en lugar de archivos
+    #[serde(default)]
+    images_in_database: bool,
+}
+
+fn get_settings_file_path(app_handle: &AppHandle) -> Result<PathBuf, AppError> {
+    let mut settings_path = get_app_data_dir(app_handle)?;
+    settings_path.push("settings.json");
+    Ok(settings_path)
+}
+
+fn load_local_settings(app_handle: &AppHandle) -> Result<LocalSettings, AppError> {
+    let Ok(contents) = fs::read_to_string(get_settings_file_path(app_handle)?) else {
+        return Ok(LocalSettings::default());
+    };
+    Ok(serde_json::from_str(&contents).unwrap_or_else(|e| {
+        eprintln!("settings.json no es válido, se usan los valores por defecto: {}", e);
+        LocalSettings::default()
+    }))
+}
+
+fn save_local_settings(app_handle: &AppHandle, settings: &LocalSettings) -> Result<(), AppError> {
+    let contents = serde_json::to_string_pretty(settings).map_err(|e| AppError::Io(e.to_string()))?;
+    fs::write(get_settings_file_path(app_handle)?, contents)?;
+    Ok(())
+}
+
+// La ubicación elegida con set_database_path, o la predeterminada
+fn get_db_file_path(app_handle: &AppHandle) -> Result<PathBuf, AppError> {
+    if let Some(path) = load_local_settings(app_handle)?.database_path {
+        return Ok(PathBuf::from(path));
+    }
+
+    let mut db_path = get_app_data_dir(app_handle)?;
+    db_path.push("inventario.db");
+    Ok(db_path)
+}
+
+// Dónde abrir la base de datos. Memory no toca el directorio de datos, para
+// probar la lógica de los comandos contra una base vacía.
+pub enum DbLocation {
+    File(PathBuf),
+    Memory,
+}
+
+// La base de datos de la aplicación, creando antes el directorio de datos
+fn app_db_location(app_handle: &AppHandle) -> Result<DbLocation, AppError> {
+    let data_dir = get_app_data_dir(app_handle)?;
+    fs::create_dir_all(&data_dir).map_err(|e| {
+        AppError::Io(format!("No se pudo crear el directorio de datos {}: {}", data_dir.display(), e))
+    })?;
+
+    Ok(DbLocation::File(get_db_file_path(app_handle)?))
+}
+
+fn init_database(location: &DbLocation) -> Result<DbPool, AppError> {
+    match location {
+        DbLocation::File(db_path) => open_pool(db_path),
+        DbLocation::Memory => open_memory_pool(),
+    }
+}
+
+// Cada conexión en memoria es una base de datos distinta, así que el pool
+// conserva una sola que nunca se recicla
+fn open_memory_pool() -> Result<DbPool, AppError> {
+    let manager = SqliteConnectionManager::memory().with_init(run_migrations);
+    let pool = r2d2::Pool::builder()
+        .max_size(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .build(manager)?;
+
+    Ok(pool)
+}
+
+// El esquema y el modo WAL (que queda guardado en el archivo) se preparan con
+// una conexión propia; las del pool solo necesitan busy_timeout
+fn open_pool(db_path: &Path) -> Result<DbPool, AppError> {
+    open_database(db_path)?;
+
+    let manager = SqliteConnectionManager::file(db_path)
+        .with_init(|conn| conn.busy_timeout(std::time::Duration::from_millis(5000)));
+    let pool = r2d2::Pool::builder().max_size(DB_POOL_SIZE).build(manager)?;
+
+    Ok(pool)
+}
+
+// Abrir la base de datos y crear o actualizar el esquema
+fn open_database(db_path: &Path) -> Result<Connection> {
+    let mut conn = Connection::open(db_path)?;
+
+    // WAL permite leer mientras se escribe y busy_timeout reintenta en lugar
+    // de fallar con "database is locked"
+    let journal_mode: String = conn.query_row("PRAGMA journal_mode=WAL", [], |row| row.get(0))?;
+    if !journal_mode.eq_ignore_ascii_case("wal") {
+        eprintln!("No se pudo activar el modo WAL (journal_mode = {})", journal_mode);
+    }
+    conn.busy_timeout(std::time::Duration::from_millis(5000))?;
+
+    run_migrations(&mut conn)?;
+
+    Ok(conn)
+}
+
+// Cada migración se aplica una sola vez y en orden; PRAGMA user_version guarda
+// cuántas se han aplicado. Las migraciones nuevas se agregan al final.
+const MIGRATIONS: &[fn(&Connection) -> Result<()>] = &[
+    migration_quantities,
+    migration_updated_at,
+    migration_category,
+    migration_stock_movements,
+    migration_thumbnails,
+    migration_indexes,
+    migration_soft_delete,
+    migration_name_normalized,
+    migration_prices,
+    migration_suppliers,
+    migration_fts,
+    migration_descripcion,
+    migration_codigo_barras,
+    migration_unidad,
+    migration_utc_timestamps,
+    migration_version,
+    migration_tags,
+    migration_punto_reorden,
+    migration_item_images,
+    migration_archived,
+    migration_settings,
+    migration_capacidad_maxima,
+    migration_count_sessions,
+    migration_image_source_mime,
+    migration_utc_created_at_default,
+    migration_real_quantities,
+];
+
+fn run_migrations(conn: &mut Connection) -> Result<()> {
+    let applied: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(applied.max(0) as usize) {
+        let tx = conn.transaction()?;
+        migration(&tx)?;
+        tx.pragma_update(None, "user_version", (index + 1) as i64)?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
+
+// SQLite no permite cambiar el tipo ni el valor por defecto de una columna: la
+// tabla se crea de nuevo con definition, se copian las filas con sus ids y se
+// vuelven a crear sus índices y triggers
+fn rebuild_table(conn: &Connection, table: &str, definition: &str) -> Result<()> {
+    let dependents = conn
+        .prepare("SELECT sql FROM sqlite_master WHERE tbl_name = ?1 AND type IN ('index', 'trigger') AND sql IS NOT NULL")?
+        .query_map([table], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>>>()?;
+    // Los ids de artículos eliminados no deben volver a usarse: el historial
+    // los sigue nombrando
+    let sequence: Option<i64> = conn
+        .query_row("SELECT seq FROM sqlite_sequence WHERE name = ?1", [table], |row| row.get(0))
+        .optional()?;
+
+    let rebuilt = format!("{}_rebuild", table);
+    conn.execute(&format!("CREATE TABLE {} ({})", rebuilt, definition), [])?;
+    let columns = conn
+        .prepare("SELECT name FROM pragma_table_info(?1)")?
+        .query_map([&rebuilt], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>>>()?
+        .join(", ");
+    conn.execute(&format!("INSERT INTO {rebuilt} ({columns}) SELECT {columns} FROM {table}"), [])?;
+    conn.execute(&format!("DROP TABLE {}", table), [])?;
+    conn.execute(&format!("ALTER TABLE {} RENAME TO {}", rebuilt, table), [])?;
+
+    for sql in dependents {
+        conn.execute_batch(&sql)?;
+    }
+    if let Some(seq) = sequence {
+        let updated = conn.execute(
+            "UPDATE sqlite_sequence SET seq = MAX(seq, ?2) WHERE name = ?1",
+            params![table, seq],
+        )?;
+        if updated == 0 {
+            conn.execute("INSERT INTO sqlite_sequence (name, seq) VALUES (?1, ?2)", params![table, seq])?;
+        }
+    }
+
+    Ok(())
+}
+
+// Las bases creadas antes de versionar el esquema (user_version = 0) pueden
+// tener ya algunas de las columnas de las primeras migraciones
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2)",
+        params![table, column],
+        |row| row.get(0),
+    )?;
+
+    if !exists {
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
+    }
+
+    Ok(())
+}
+
+fn migration_quantities(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS inventory (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            image_path TEXT,
+            cantidad_necesaria INTEGER NOT NULL DEFAULT 0,
+            cantidad_disponible INTEGER NOT NULL DEFAULT 0,
+            created_at DATETIME DEFAULT (datetime('now', 'localtime'))
+        )",
+        [],
+    )?;
+
+    add_column_if_missing(conn, "inventory", "cantidad_necesaria", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(conn, "inventory", "cantidad_disponible", "INTEGER NOT NULL DEFAULT 0")?;
+
+    Ok(())
+}
+
+fn migration_updated_at(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "inventory", "updated_at", "DATETIME")?;
+
+    // Las filas anteriores a updated_at toman su fecha de creación
+    conn.execute(
+        "UPDATE inventory SET updated_at = created_at WHERE updated_at IS NULL",
+        [],
+    )?;
+
+    Ok(())
+}
+
+fn migration_category(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "inventory", "category", "TEXT")
+}
+
+fn migration_stock_movements(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS stock_movements (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            item_id INTEGER NOT NULL,
+            delta INTEGER NOT NULL,
+            reason TEXT,
+            previous_qty INTEGER NOT NULL,
+            new_qty INTEGER NOT NULL,
+            timestamp DATETIME NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_stock_movements_item_id ON stock_movements(item_id)",
+        [],
+    )?;
+
+    Ok(())
+}
+
+fn migration_thumbnails(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "inventory", "thumbnail_path", "TEXT")
+}
+
+fn migration_indexes(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_inventory_created_at ON inventory(created_at)",
+        [],
+    )?;
+    // NOCASE para que sirva a las comparaciones de nombre sin distinguir mayúsculas
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_inventory_name ON inventory(name COLLATE NOCASE)",
+        [],
+    )?;
+
+    Ok(())
+}
+
+fn migration_soft_delete(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "inventory", "deleted_at", "DATETIME")
+}
+
+fn migration_name_normalized(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "inventory", "name_normalized", "TEXT")?;
+
+    // Calcular name_normalized para las filas existentes
+    let pending: Vec<(i64, String)> = conn
+        .prepare("SELECT id, name FROM inventory WHERE name_normalized IS NULL")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    for (id, name) in pending {
+        conn.execute(
+            "UPDATE inventory SET name_normalized = ?1 WHERE id = ?2",
+            params![normalize_name(&name), id],
+        )?;
+    }
+
+    Ok(())
+}
+
+fn migration_prices(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "inventory", "precio_unitario", "REAL")?;
+    add_column_if_missing(conn, "inventory", "costo_unitario", "REAL")?;
+
+    Ok(())
+}
+
+fn migration_suppliers(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS suppliers (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            contact TEXT,
+            phone TEXT
+        )",
+        [],
+    )?;
+    add_column_if_missing(
+        conn,
+        "inventory",
+        "supplier_id",
+        "INTEGER REFERENCES suppliers(id) ON DELETE SET NULL",
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_inventory_supplier_id ON inventory(supplier_id)",
+        [],
+    )?;
+
+    Ok(())
+}
+
+// Índice de texto completo sobre el nombre. Si SQLite se compiló sin FTS5 se
+// omite y search_fts recurre a LIKE.
+fn migration_fts(conn: &Connection) -> Result<()> {
+    if let Err(e) = conn.execute(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS inventory_fts USING fts5(
+            name,
+            content='inventory',
+            content_rowid='id',
+            tokenize='unicode61 remove_diacritics 2'
+        )",
+        [],
+    ) {
+        eprintln!("Advertencia: FTS5 no está disponible, se usará búsqueda LIKE: {}", e);
+        return Ok(());
+    }
+
+    conn.execute_batch(
+        "CREATE TRIGGER IF NOT EXISTS inventory_fts_insert AFTER INSERT ON inventory BEGIN
+            INSERT INTO inventory_fts(rowid, name) VALUES (new.id, new.name);
+        END;
+        CREATE TRIGGER IF NOT EXISTS inventory_fts_delete AFTER DELETE ON inventory BEGIN
+            INSERT INTO inventory_fts(inventory_fts, rowid, name) VALUES ('delete', old.id, old.name);
+        END;
+        CREATE TRIGGER IF NOT EXISTS inventory_fts_update AFTER UPDATE OF name ON inventory BEGIN
+            INSERT INTO inventory_fts(inventory_fts, rowid, name) VALUES ('delete', old.id, old.name);
+            INSERT INTO inventory_fts(rowid, name) VALUES (new.id, new.name);
+        END;
+        INSERT INTO inventory_fts(inventory_fts) VALUES ('rebuild');",
+    )?;
+
+    Ok(())
+}
+
+// La descripción también entra en el índice de texto completo, así que la
+// tabla FTS se vuelve a crear con ambas columnas
+fn migration_descripcion(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "inventory", "descripcion", "TEXT")?;
+
+    conn.execute_batch(
+        "DROP TRIGGER IF EXISTS inventory_fts_insert;
+        DROP TRIGGER IF EXISTS inventory_fts_delete;
+        DROP TRIGGER IF EXISTS inventory_fts_update;
+        DROP TABLE IF EXISTS inventory_fts;",
+    )?;
+
+    if let Err(e) = conn.execute(
+        "CREATE VIRTUAL TABLE inventory_fts USING fts5(
+            name,
+            descripcion,
+            content='inventory',
+            content_rowid='id',
+            tokenize='unicode61 remove_diacritics 2'
+        )",
+        [],
+    ) {
+        eprintln!("Advertencia: FTS5 no está disponible, se usará búsqueda LIKE: {}", e);
+        return Ok(());
+    }
+
+    conn.execute_batch(
+        "CREATE TRIGGER inventory_fts_insert AFTER INSERT ON inventory BEGIN
+            INSERT INTO inventory_fts(rowid, name, descripcion) VALUES (new.id, new.name, new.descripcion);
+        END;
+        CREATE TRIGGER inventory_fts_delete AFTER DELETE ON inventory BEGIN
+            INSERT INTO inventory_fts(inventory_fts, rowid, name, descripcion)
+                VALUES ('delete', old.id, old.name, old.descripcion);
+        END;
+        CREATE TRIGGER inventory_fts_update AFTER UPDATE OF name, descripcion ON inventory BEGIN
+            INSERT INTO inventory_fts(inventory_fts, rowid, name, descripcion)
+                VALUES ('delete', old.id, old.name, old.descripcion);
+            INSERT INTO inventory_fts(rowid, name, descripcion) VALUES (new.id, new.name, new.descripcion);
+        END;
+        INSERT INTO inventory_fts(inventory_fts) VALUES ('rebuild');",
+    )?;
+
+    Ok(())
+}
+
+// Índice único: SQLite admite varios NULL, así que solo se repite la ausencia de código
+fn migration_codigo_barras(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "inventory", "codigo_barras", "TEXT")?;
+    conn.execute(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_inventory_codigo_barras ON inventory(codigo_barras)",
+        [],
+    )?;
+
+    Ok(())
+}
+
+fn migration_unidad(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "inventory", "unidad", "TEXT")
+}
+
+// Fecha guardada por versiones anteriores en hora local sin zona
+fn local_timestamp_to_utc(value: &str) -> Option<String> {
+    use chrono::TimeZone;
+
+    let naive = chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").ok()?;
+    // En el salto de horario de verano la hora local puede no existir; se toma tal cual
+    let utc = match chrono::Local.from_local_datetime(&naive).earliest() {
+        Some(local) => local.with_timezone(&chrono::Utc),
+        None => chrono::Utc.from_utc_datetime(&naive),
+    };
+
+    Some(utc.format(TIMESTAMP_FORMAT).to_string())
+}
+
+fn migration_utc_timestamps(conn: &Connection) -> Result<()> {
+    let columns = [
+        ("inventory", "created_at"),
+        ("inventory", "updated_at"),
+        ("inventory", "deleted_at"),
+        ("stock_movements", "timestamp"),
+    ];
+
+    for (table, column) in columns {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT rowid, {column} FROM {table} WHERE {column} IS NOT NULL"
+        ))?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for (rowid, value) in rows {
+            if let Some(utc) = local_timestamp_to_utc(&value) {
+                conn.execute(
+                    &format!("UPDATE {table} SET {column} = ?1 WHERE rowid = ?2"),
+                    params![utc, rowid],
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Contador para el bloqueo optimista de update_item
+fn migration_version(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "inventory", "version", "INTEGER NOT NULL DEFAULT 1")
+}
+
+// Las asociaciones de un artículo se borran con él mediante un trigger, ya que
+// las claves foráneas no están activadas en la conexión
+fn migration_tags(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS tags (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE COLLATE NOCASE
+        );
+        CREATE TABLE IF NOT EXISTS item_tags (
+            item_id INTEGER NOT NULL REFERENCES inventory(id) ON DELETE CASCADE,
+            tag_id INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+            PRIMARY KEY (item_id, tag_id)
+        );
+        CREATE INDEX IF NOT EXISTS idx_item_tags_tag_id ON item_tags(tag_id);
+        CREATE TRIGGER IF NOT EXISTS item_tags_item_delete AFTER DELETE ON inventory BEGIN
+            DELETE FROM item_tags WHERE item_id = old.id;
+        END;",
+    )
+}
+
+fn migration_punto_reorden(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "inventory", "punto_reorden", "INTEGER")
+}
+
+// Un artículo está bajo de stock por debajo de su punto de reorden, o del
+// low_stock_threshold_percent de la cantidad necesaria si no tiene uno. El
+// margen es QUANTITY_EPSILON.
+fn low_stock_condition() -> String {
+    format!(
+        "cantidad_disponible < COALESCE(punto_reorden, cantidad_necesaria * COALESCE(
+            (SELECT CAST(value AS REAL) FROM settings WHERE key = 'low_stock_threshold_percent'), {percent}) / 100.0) - {epsilon}",
+        percent = LOW_STOCK_DEFAULT_PERCENT,
+        epsilon = QUANTITY_EPSILON
+    )
+}
+
+// Las cantidades admiten decimales (2.5 litros, 1.75 kg) y se guardan
+// redondeadas a QUANTITY_DECIMALS.
+const QUANTITY_DECIMALS: i32 = 3;
+
+// Media unidad del último decimal; las comparaciones la usan como margen para
+// que una suma como 0.1 + 0.2 no quede por debajo de 0.3
+const QUANTITY_EPSILON: f64 = 0.0005;
+
+fn round_quantity(value: f64) -> f64 {
+    let factor = 10f64.powi(QUANTITY_DECIMALS);
+    (value * factor).round() / factor
+}
+
+// Imágenes guardadas como BLOB, una por artículo. La miniatura sigue siendo un
+// archivo porque se puede volver a generar.
+fn migration_item_images(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS item_images (
+            item_id INTEGER PRIMARY KEY REFERENCES inventory(id) ON DELETE CASCADE,
+            mime TEXT NOT NULL,
+            data BLOB NOT NULL
+        );
+        CREATE TRIGGER IF NOT EXISTS item_images_item_delete AFTER DELETE ON inventory BEGIN
+            DELETE FROM item_images WHERE item_id = old.id;
+        END;",
+    )
+}
+
+// Archivado no es lo mismo que la papelera: son artículos que se guardan a
+// propósito fuera del listado principal, por ejemplo los de temporada
+fn migration_archived(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "inventory", "archived", "INTEGER NOT NULL DEFAULT 0")
+}
+
+// Preferencias de la aplicación (clave y valor). A diferencia de LocalSettings
+// viajan con la base de datos en los respaldos.
+fn migration_settings(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );",
+    )
+}
+
+// Capacidad física del lugar donde se guarda; NULL es sin límite
+fn migration_capacidad_maxima(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "inventory", "capacidad_maxima", "INTEGER")
+}
+
+// Las líneas guardan el nombre para que el historial del conteo se pueda leer
+// aunque el artículo se elimine después
+fn migration_count_sessions(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS count_sessions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            started_at TEXT NOT NULL,
+            finished_at TEXT,
+            zeroed INTEGER NOT NULL DEFAULT 0
+        );
+        CREATE TABLE IF NOT EXISTS count_lines (
+            session_id INTEGER NOT NULL,
+            item_id INTEGER NOT NULL,
+            item_name TEXT NOT NULL,
+            snapshot_qty INTEGER NOT NULL,
+            counted_qty INTEGER,
+            final_qty INTEGER,
+            variance INTEGER,
+            PRIMARY KEY (session_id, item_id)
+        );",
+    )
+}
+
+fn migration_image_source_mime(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "inventory", "image_source_mime", "TEXT")
+}
+
+// created_at tomaba por defecto la hora local; ahora es UTC como el resto. Las
+// filas guardadas con el valor anterior se pasan a UTC igual que en
+// migration_utc_timestamps.
+fn migration_utc_created_at_default(conn: &Connection) -> Result<()> {
+    rebuild_table(
+        conn,
+        "inventory",
+        "id INTEGER PRIMARY KEY AUTOINCREMENT,
+         name TEXT NOT NULL,
+         image_path TEXT,
+         cantidad_necesaria INTEGER NOT NULL DEFAULT 0,
+         cantidad_disponible INTEGER NOT NULL DEFAULT 0,
+         created_at DATETIME DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+         updated_at DATETIME,
+         category TEXT,
+         thumbnail_path TEXT,
+         deleted_at DATETIME,
+         name_normalized TEXT,
+         precio_unitario REAL,
+         costo_unitario REAL,
+         supplier_id INTEGER REFERENCES suppliers(id) ON DELETE SET NULL,
+         descripcion TEXT,
+         codigo_barras TEXT,
+         unidad TEXT,
+         version INTEGER NOT NULL DEFAULT 1,
+         punto_reorden INTEGER,
+         archived INTEGER NOT NULL DEFAULT 0,
+         capacidad_maxima INTEGER,
+         image_source_mime TEXT",
+    )?;
+
+    migration_utc_timestamps(conn)
+}
+
+// Las cantidades admiten decimales: las columnas pasan de INTEGER a REAL para
+// que los enteros guardados antes se lean igual que los nuevos
+fn migration_real_quantities(conn: &Connection) -> Result<()> {
+    rebuild_table(
+        conn,
+        "inventory",
+        "id INTEGER PRIMARY KEY AUTOINCREMENT,
+         name TEXT NOT NULL,
+         image_path TEXT,
+         cantidad_necesaria REAL NOT NULL DEFAULT 0,
+         cantidad_disponible REAL NOT NULL DEFAULT 0,
+         created_at DATETIME DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+         updated_at DATETIME,
+         category TEXT,
+         thumbnail_path TEXT,
+         deleted_at DATETIME,
+         name_normalized TEXT,
+         precio_unitario REAL,
+         costo_unitario REAL,
+         supplier_id INTEGER REFERENCES suppliers(id) ON DELETE SET NULL,
+         descripcion TEXT,
+         codigo_barras TEXT,
+         unidad TEXT,
+         version INTEGER NOT NULL DEFAULT 1,
+         punto_reorden REAL,
+         archived INTEGER NOT NULL DEFAULT 0,
+         capacidad_maxima REAL,
+         image_source_mime TEXT",
+    )?;
+    rebuild_table(
+        conn,
+        "stock_movements",
+        "id INTEGER PRIMARY KEY AUTOINCREMENT,
+         item_id INTEGER NOT NULL,
+         delta REAL NOT NULL,
+         reason TEXT,
+         previous_qty REAL NOT NULL,
+         new_qty REAL NOT NULL,
+         timestamp DATETIME NOT NULL",
+    )?;
+    rebuild_table(
+        conn,
+        "count_lines",
+        "session_id INTEGER NOT NULL,
+         item_id INTEGER NOT NULL,
+         item_name TEXT NOT NULL,
+         snapshot_qty REAL NOT NULL,
+         counted_qty REAL,
+         final_qty REAL,
+         variance REAL,
+         PRIMARY KEY (session_id, item_id)",
+    )
+}
+
+#[tauri::command]
+fn get_all_items(include_archived: Option<bool>, state: State<AppState>) -> Result<Vec<InventoryItem>, AppError> {
+    let db = state.conn()?;
+    Ok(query_all_items(&db, include_archived.unwrap_or(false))?)
+}
+
+fn query_all_items(db: &Connection, include_archived: bool) -> Result<Vec<InventoryItem>> {
+    let mut stmt = db.prepare(&format!(
+        "SELECT {} FROM inventory WHERE deleted_at IS NULL AND (?1 OR archived = 0) ORDER BY created_at DESC, id DESC",
+        ITEM_COLUMNS
+    ))?;
+
+    let items = stmt
+        .query_map([include_archived], item_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(items)
+}
+
+#[tauri::command]
+fn get_archived_items(state: State<AppState>) -> Result<Vec<InventoryItem>, AppError> {
+    let db = state.conn()?;
+    Ok(query_archived_items(&db)?)
+}
+
+fn query_archived_items(db: &Connection) -> Result<Vec<InventoryItem>> {
+    let mut stmt = db.prepare(&format!(
+        "SELECT {} FROM inventory WHERE deleted_at IS NULL AND archived = 1 ORDER BY name COLLATE NOCASE, id",
+        ITEM_COLUMNS
+    ))?;
+
+    let items = stmt
+        .query_map([], item_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(items)
+}
+
+#[tauri::command]
+fn set_archived(id: i64, archived: bool, state: State<AppState>) -> Result<InventoryItem, AppError> {
+    let db = state.write_conn()?;
+    let item = update_archived(&db, id, archived)?;
+
+    emit_inventory_changed(&state.app_handle, id, "archive");
+
+    Ok(item)
+}
+
+// Parte de set_archived que solo toca la base de datos
+fn update_archived(db: &Connection, id: i64, archived: bool) -> Result<InventoryItem, AppError> {
+    let updated = db.execute(
+        "UPDATE inventory SET archived = ?1, updated_at = ?2, version = version + 1 WHERE id = ?3 AND deleted_at IS NULL",
+        params![archived, now_timestamp(), id],
+    )?;
+    if updated == 0 {
+        return Err(AppError::NotFound(id));
+    }
+
+    fetch_item(db, id)
+}
+
+// Total sin transferir las filas; no cuenta la papelera ni, salvo que se
+// pida, los archivados
+#[tauri::command]
+fn count_items(include_archived: Option<bool>, state: State<AppState>) -> Result<i64, AppError> {
+    let db = state.conn()?;
+
+    let count = db.query_row(
+        "SELECT COUNT(*) FROM inventory WHERE deleted_at IS NULL AND (?1 OR archived = 0)",
+        [include_archived.unwrap_or(false)],
+        |row| row.get(0),
+    )?;
+
+    Ok(count)
+}
+
+fn fetch_item(db: &Connection, id: i64) -> Result<InventoryItem, AppError> {
+    db.query_row(
+        &format!("SELECT {} FROM inventory WHERE id = ?1 AND deleted_at IS NULL", ITEM_COLUMNS),
+        [id],
+        item_from_row,
+    )
+    .optional()?
+    .ok_or(AppError::NotFound(id))
+}
+
+#[tauri::command]
+fn get_item(id: i64, state: State<AppState>) -> Result<InventoryItem, AppError> {
+    let db = state.conn()?;
+    fetch_item(&db, id)
+}
+
+// Los ids que no existen simplemente no aparecen en el resultado
+#[tauri::command]
+fn get_items_by_ids(ids: Vec<i64>, state: State<AppState>) -> Result<Vec<InventoryItem>, AppError> {
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let placeholders = vec!["?"; ids.len()].join(", ");
+    let db = state.conn()?;
+    let mut stmt = db.prepare(&format!(
+        "SELECT {} FROM inventory WHERE id IN ({}) AND deleted_at IS NULL ORDER BY id",
+        ITEM_COLUMNS, placeholders
+    ))?;
+
+    let items = stmt
+        .query_map(rusqlite::params_from_iter(&ids), item_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(items)
+}
+
+#[tauri::command]
+fn get_items_paginated(
+    limit: i64,
+    offset: i64,
+    include_archived: Option<bool>,
+    state: State<AppState>,
+) -> Result<PaginatedItems, AppError> {
+    let db = state.conn()?;
+    let include_archived = include_archived.unwrap_or(false);
+
+    let total: i64 = db.query_row(
+        "SELECT COUNT(*) FROM inventory WHERE deleted_at IS NULL AND (?1 OR archived = 0)",
+        [include_archived],
+        |row| row.get(0),
+    )?;
+
+    let mut stmt = db
+        .prepare(&format!(
+            "SELECT {} FROM inventory WHERE deleted_at IS NULL AND (?3 OR archived = 0)
+             ORDER BY created_at DESC, id DESC LIMIT ?1 OFFSET ?2",
+            ITEM_COLUMNS
+        ))?;
+
+    let items = stmt
+        .query_map(params![limit.max(0), offset.max(0), include_archived], item_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(PaginatedItems { items, total })
+}
+
+// Máximo de artículos del listado de recientes
+const RECENT_ITEMS_MAX: i64 = 100;
+
+#[tauri::command]
+fn get_recent_items(
+    limit: i64,
+    include_archived: Option<bool>,
+    state: State<AppState>,
+) -> Result<Vec<InventoryItem>, AppError> {
+    let db = state.conn()?;
+    let mut stmt = db.prepare(&format!(
+        "SELECT {} FROM inventory WHERE deleted_at IS NULL AND (?2 OR archived = 0)
+         ORDER BY created_at DESC, id DESC LIMIT ?1",
+        ITEM_COLUMNS
+    ))?;
+
+    let items = stmt
+        .query_map(params![limit.clamp(0, RECENT_ITEMS_MAX), include_archived.unwrap_or(false)], item_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(items)
+}
+
+// Acepta un timestamp completo o solo la fecha ("2025-01-31"); una fecha sola
+// cubre el día entero en la hora local
+fn parse_range_bound(value: &str, end_of_day: bool) -> Option<String> {
+    let value = value.trim();
+    if chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok() {
+        let time = if end_of_day { "23:59:59" } else { "00:00:00" };
+        return local_timestamp_to_utc(&format!("{} {}", value, time));
+    }
+    parse_timestamp(value)
+}
+
+// Extremos de un periodo ya convertidos a UTC
+fn parse_date_range(start: &str, end: &str) -> Result<(String, String), AppError> {
+    let start_ts = parse_range_bound(start, false)
+        .ok_or_else(|| AppError::InvalidInput(format!("La fecha de inicio no es válida: \"{}\"", start)))?;
+    let end_ts = parse_range_bound(end, true)
+        .ok_or_else(|| AppError::InvalidInput(format!("La fecha de fin no es válida: \"{}\"", end)))?;
+    // Con el mismo formato, el orden de los textos es el de las fechas
+    if start_ts > end_ts {
+        return Err(AppError::InvalidInput(
+            "La fecha de inicio no puede ser posterior a la fecha de fin".to_string(),
+        ));
+    }
+    Ok((start_ts, end_ts))
+}
+
+// Artículos dados de alta en un periodo, en orden cronológico; ambos extremos
+// se incluyen
+#[tauri::command]
+fn get_items_created_between(
+    start: String,
+    end: String,
+    state: State<AppState>,
+) -> Result<Vec<InventoryItem>, AppError> {
+    let (start_ts, end_ts) = parse_date_range(&start, &end)?;
+
+    let db = state.conn()?;
+    let mut stmt = db.prepare(&format!(
+        "SELECT {} FROM inventory WHERE deleted_at IS NULL AND created_at BETWEEN ?1 AND ?2 ORDER BY created_at ASC, id ASC",
+        ITEM_COLUMNS
+    ))?;
+
+    let items = stmt
+        .query_map(params![start_ts, end_ts], item_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(items)
+}
+
+// Solo columnas conocidas llegan a la consulta; cualquier otro valor ordena
+// por fecha de creación descendente
+fn sort_clause(sort_by: &str, ascending: bool) -> String {
+    let column = match sort_by {
+        "name" => "name COLLATE NOCASE",
+        "created_at" => "created_at",
+        "cantidad_disponible" => "cantidad_disponible",
+        "cantidad_necesaria" => "cantidad_necesaria",
+        _ => return "created_at DESC, id DESC".to_string(),
+    };
+
+    // id desempata las filas con el mismo valor para que el orden sea estable
+    let direction = if ascending { "ASC" } else { "DESC" };
+    format!("{} {}, id {}", column, direction, direction)
+}
+
+#[tauri::command]
+fn get_items_sorted(sort_by: String, ascending: bool, state: State<AppState>) -> Result<Vec<InventoryItem>, AppError> {
+    let db = state.conn()?;
+    let filter = ItemFilter {
+        sort_by: Some(sort_by),
+        ascending,
+        include_archived: true,
+        ..Default::default()
+    };
+
+    Ok(query_items(&db, &filter)?)
+}
+
+#[tauri::command]
+fn get_items_filtered(filter: ItemFilter, state: State<AppState>) -> Result<Vec<InventoryItem>, AppError> {
+    let db = state.conn()?;
+    Ok(query_items(&db, &filter)?)
+}
+
+// Consulta del listado con sus parámetros; los valores del usuario siempre van
+// como parámetros y el orden solo admite las columnas de sort_clause
+fn build_item_query(filter: &ItemFilter) -> (String, Vec<rusqlite::types::Value>) {
+    let mut conditions = vec!["deleted_at IS NULL".to_string()];
+    let mut values: Vec<rusqlite::types::Value> = Vec::new();
+
+    if let Some(query) = filter.query.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+        values.push(format!("%{}%", escape_like(&normalize_name(query))).into());
+        conditions.push(format!("name_normalized LIKE ?{} ESCAPE '\\'", values.len()));
+    }
+    if let Some(category) = normalize_optional_text(filter.category.clone()) {
+        values.push(category.into());
+        conditions.push(format!("category = ?{} COLLATE NOCASE", values.len()));
+    }
+    if filter.low_stock_only {
+        conditions.push(low_stock_condition());
+    }
+    if !filter.include_archived {
+        conditions.push("archived = 0".to_string());
+    }
+
+    let order = match filter.sort_by.as_deref() {
+        Some(sort_by) => sort_clause(sort_by, filter.ascending),
+        None => "created_at DESC, id DESC".to_string(),
+    };
+
+    let sql = format!(
+        "SELECT {} FROM inventory WHERE {} ORDER BY {}",
+        ITEM_COLUMNS,
+        conditions.join(" AND "),
+        order
+    );
+
+    (sql, values)
+}
+
+fn query_items(db: &Connection, filter: &ItemFilter) -> Result<Vec<InventoryItem>> {
+    let (sql, values) = build_item_query(filter);
+    let mut stmt = db.prepare(&sql)?;
+
+    let items = stmt
+        .query_map(rusqlite::params_from_iter(values), item_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(items)
+}
+
+// Un texto vacío equivale a no tenerlo (categoría, descripción)
+fn normalize_optional_text(value: Option<String>) -> Option<String> {
+    value
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+// Minúsculas y sin acentos, para que "solucion" encuentre "Solución"
+fn normalize_name(name: &str) -> String {
+    use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+    name.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .collect::<String>()
+        .to_lowercase()
+}
+
+// Escapar los comodines de LIKE para que "50%" se busque literalmente
+fn escape_like(query: &str) -> String {
+    let mut escaped = String::with_capacity(query.len());
+    for c in query.chars() {
+        if c == '\\' || c == '%' || c == '_' {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[tauri::command]
+fn search_items(query: String, state: State<AppState>) -> Result<Vec<InventoryItem>, AppError> {
+    let db = state.conn()?;
+    let items = search_like(&db, &query)?;
+
+    Ok(items)
+}
+
+fn search_like(db: &Connection, query: &str) -> Result<Vec<InventoryItem>> {
+    let filter = ItemFilter {
+        query: Some(query.to_string()),
+        include_archived: true,
+        ..Default::default()
+    };
+
+    query_items(db, &filter)
+}
+
+// Recalcula name_normalized de todas las filas (incluida la papelera) y, si
+// existe, reconstruye el índice FTS. Útil después de importar en lote; se
+// puede repetir sin efectos secundarios.
+#[tauri::command]
+fn rebuild_search_index(state: State<AppState>) -> Result<i32, AppError> {
+    let mut db = state.write_conn()?;
+
+    with_transaction(&mut db, |tx| {
+        let rows: Vec<(i64, String)> = tx
+            .prepare("SELECT id, name FROM inventory")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut stmt = tx.prepare("UPDATE inventory SET name_normalized = ?1 WHERE id = ?2")?;
+        for (id, name) in &rows {
+            stmt.execute(params![normalize_name(name), id])?;
+        }
+
+        if fts_available(tx)? {
+            tx.execute("INSERT INTO inventory_fts(inventory_fts) VALUES ('rebuild')", [])?;
+        }
+
+        Ok(rows.len() as i32)
+    })
+}
+
+fn fts_available(db: &Connection) -> Result<bool> {
+    db.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'inventory_fts')",
+        [],
+        |row| row.get(0),
+    )
+}
+
+// Cada palabra se busca como prefijo literal, así la sintaxis de FTS5
+// (comillas, operadores) en la entrada del usuario no provoca errores
+fn fts_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Búsqueda por texto completo ordenada por relevancia (bm25)
+#[tauri::command]
+fn search_fts(query: String, state: State<AppState>) -> Result<Vec<InventoryItem>, AppError> {
+    let db = state.conn()?;
+
+    let match_query = fts_query(&query);
+    if match_query.is_empty() {
+        return Ok(search_like(&db, "")?);
+    }
+
+    if !fts_available(&db)? {
+        eprintln!("Advertencia: índice FTS5 no disponible, se usa búsqueda LIKE");
+        return Ok(search_like(&db, &query)?);
+    }
+
+    let mut stmt = db.prepare(&format!(
+        "SELECT {} FROM inventory
+         JOIN (SELECT rowid AS fts_id, bm25(inventory_fts) AS rank FROM inventory_fts WHERE inventory_fts MATCH ?1)
+           ON id = fts_id
+         WHERE deleted_at IS NULL
+         ORDER BY rank, id",
+        ITEM_COLUMNS
+    ))?;
+
+    let items = stmt
+        .query_map([match_query], item_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(items)
+}
+
+#[tauri::command]
+fn get_item_by_barcode(code: String, state: State<AppState>) -> Result<Option<InventoryItem>, AppError> {
+    let code = code.trim();
+    if code.is_empty() {
+        return Ok(None);
+    }
+
+    let db = state.conn()?;
+    let item = db
+        .query_row(
+            &format!(
+                "SELECT {} FROM inventory WHERE codigo_barras = ?1 AND deleted_at IS NULL",
+                ITEM_COLUMNS
+            ),
+            [code],
+            item_from_row,
+        )
+        .optional()?;
+
+    Ok(item)
+}
+
+// "Jeringa 5ml" y "jeringa 5 ml" se consideran el mismo artículo
+#[tauri::command]
+fn find_duplicates(state: State<AppState>) -> Result<Vec<DuplicateGroup>, AppError> {
+    let db = state.conn()?;
+    let mut stmt = db.prepare(&format!(
+        "SELECT {} FROM inventory WHERE deleted_at IS NULL ORDER BY created_at, id",
+        ITEM_COLUMNS
+    ))?;
+
+    let items = stmt
+        .query_map([], item_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut groups: std::collections::BTreeMap<String, Vec<InventoryItem>> = std::collections::BTreeMap::new();
+    for item in items {
+        let key: String = normalize_name(&item.name)
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect();
+        groups.entry(key).or_default().push(item);
+    }
+
+    let duplicates = groups
+        .into_iter()
+        .filter(|(_, items)| items.len() > 1)
+        .map(|(normalized_name, items)| DuplicateGroup { normalized_name, items })
+        .collect();
+
+    Ok(duplicates)
+}
+
+// Nombre sin espacios al inicio ni al final; uno vacío no se podría encontrar
+fn validate_name(name: &str) -> Result<String, AppError> {
+    // Los caracteres de control suelen llegar al pegar texto o en importaciones
+    let name: String = name.chars().filter(|c| !c.is_control()).collect();
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::InvalidInput("El nombre del artículo no puede estar vacío".to_string()));
+    }
+    if name.chars().count() > MAX_NAME_CHARS {
+        return Err(AppError::InvalidInput(format!(
+            "El nombre del artículo no puede tener más de {} caracteres",
+            MAX_NAME_CHARS
+        )));
+    }
+    Ok(name.to_string())
+}
+
+// La columna admite negativos, pero ensuciarían los reportes y las sumas
+fn validate_quantities(cantidad_necesaria: f64, cantidad_disponible: f64) -> Result<(), AppError> {
+    validate_quantity(cantidad_necesaria)?;
+    validate_quantity(cantidad_disponible)?;
+    if cantidad_necesaria < 0.0 {
+        return Err(AppError::InvalidInput("La cantidad necesaria no puede ser negativa".to_string()));
+    }
+    if cantidad_disponible < 0.0 {
+        return Err(AppError::InvalidInput("La cantidad disponible no puede ser negativa".to_string()));
+    }
+    Ok(())
+}
+
+// NaN o infinito no se pueden comparar ni sumar en los reportes
+fn validate_quantity(value: f64) -> Result<(), AppError> {
+    if !value.is_finite() {
+        return Err(AppError::InvalidInput(format!("La cantidad no es un número válido: {}", value)));
+    }
+    Ok(())
+}
+
+fn validate_capacity(cantidad_disponible: f64, capacidad_maxima: Option<f64>) -> Result<(), AppError> {
+    let Some(capacidad) = capacidad_maxima else {
+        return Ok(());
+    };
+    validate_quantity(capacidad)?;
+    if capacidad < 0.0 {
+        return Err(AppError::InvalidInput("La capacidad máxima no puede ser negativa".to_string()));
+    }
+    if cantidad_disponible > capacidad + QUANTITY_EPSILON {
+        return Err(AppError::InvalidInput(format!(
+            "La cantidad disponible ({}) supera la capacidad máxima ({})",
+            cantidad_disponible, capacidad
+        )));
+    }
+    Ok(())
+}
+
+// Comprobar que no exista otro artículo con el mismo nombre (sin distinguir
+// mayúsculas), ignorando el artículo que se está editando
+fn ensure_unique_name(db: &Connection, name: &str, exclude_id: Option<i64>) -> Result<(), AppError> {
+    let exists: bool = db.query_row(
+        "SELECT EXISTS(SELECT 1 FROM inventory WHERE name = ?1 COLLATE NOCASE AND id IS NOT ?2 AND deleted_at IS NULL)",
+        params![name, exclude_id],
+        |row| row.get(0),
+    )?;
+
+    if exists {
+        return Err(AppError::DuplicateName(name.to_string()));
+    }
+
+    Ok(())
+}
+
+// El índice es único también para los artículos en la papelera
+fn ensure_unique_barcode(db: &Connection, code: Option<&str>, exclude_id: Option<i64>) -> Result<(), AppError> {
+    let code = match code {
+        Some(code) => code,
+        None => return Ok(()),
+    };
+
+    let exists: bool = db.query_row(
+        "SELECT EXISTS(SELECT 1 FROM inventory WHERE codigo_barras = ?1 AND id IS NOT ?2)",
+        params![code, exclude_id],
+        |row| row.get(0),
+    )?;
+
+    if exists {
+        return Err(AppError::DuplicateBarcode(code.to_string()));
+    }
+
+    Ok(())
+}
+
+fn ensure_supplier_exists(db: &Connection, supplier_id: Option<i64>) -> Result<(), AppError> {
+    let Some(supplier_id) = supplier_id else {
+        return Ok(());
+    };
+
+    let exists: bool = db.query_row(
+        "SELECT EXISTS(SELECT 1 FROM suppliers WHERE id = ?1)",
+        [supplier_id],
+        |row| row.get(0),
+    )?;
+
+    if !exists {
+        return Err(AppError::SupplierNotFound(supplier_id));
+    }
+
+    Ok(())
+}
+
+// Avisa a todas las ventanas para que refresquen; un fallo al emitir no
+// debe deshacer una operación ya guardada
+fn emit_inventory_changed(app_handle: &AppHandle, id: i64, operation: &str) {
+    let payload = InventoryChanged {
+        id,
+        operation: operation.to_string(),
+    };
+    if let Err(e) = app_handle.emit("inventory-changed", payload) {
+        eprintln!("No se pudo emitir inventory-changed: {}", e);
+    }
+}
+
+const LOW_STOCK_DEFAULT_PERCENT: f64 = 100.0;
+
+// Misma regla que low_stock_condition
+fn is_low_stock(cantidad_disponible: f64, cantidad_necesaria: f64, punto_reorden: Option<f64>, percent: f64) -> bool {
+    cantidad_disponible < punto_reorden.unwrap_or(cantidad_necesaria * percent / 100.0) - QUANTITY_EPSILON
+}
+
+fn low_stock_percent(app_handle: &AppHandle) -> f64 {
+    app_setting(app_handle, "low_stock_threshold_percent")
+        .and_then(|value| value.parse::<f64>().ok())
+        .unwrap_or(LOW_STOCK_DEFAULT_PERCENT)
+}
+
+const LOW_STOCK_CHECK_DEFAULT_MINUTES: u64 = 60;
+
+// Cada low_stock_check_minutes emite scheduled-low-stock con los artículos en
+// stock bajo, aunque la ventana no esté abierta. El intervalo se vuelve a leer
+// en cada vuelta; en pausa se revisa la preferencia cada minuto.
+async fn run_low_stock_scheduler(app_handle: AppHandle) {
+    loop {
+        let minutes = app_setting(&app_handle, "low_stock_check_minutes")
+            .and_then(|value| value.parse::<u64>().ok())
+            .unwrap_or(LOW_STOCK_CHECK_DEFAULT_MINUTES);
+        tokio::time::sleep(Duration::from_secs(60 * minutes.max(1))).await;
+        if minutes == 0 {
+            continue;
+        }
+
+        // La consulta va en un hilo aparte y la conexión vuelve al pool antes
+        // de emitir
+        let handle = app_handle.clone();
+        let result = tauri::async_runtime::spawn_blocking(move || -> Result<Option<Vec<InventoryItem>>, AppError> {
+            // Sin estado la aplicación se está cerrando
+            let Some(state) = handle.try_state::<AppState>() else {
+                return Ok(None);
+            };
+            let db = state.conn()?;
+            Ok(Some(query_low_stock_items(&db, -1)?))
+        })
+        .await;
+
+        match result {
+            Ok(Ok(Some(items))) => {
+                if items.is_empty() {
+                    continue;
+                }
+                if let Err(e) = app_handle.emit("scheduled-low-stock", &items) {
+                    eprintln!("No se pudo emitir scheduled-low-stock: {}", e);
+                }
+            }
+            Ok(Ok(None)) => break,
+            Ok(Err(e)) => eprintln!("No se pudo revisar el stock bajo: {}", e),
+            Err(e) => eprintln!("Falló la revisión programada del stock bajo: {}", e),
+        }
+    }
+}
+
+// Solo al pasar a stock bajo, no en cada guardado de un artículo que ya lo estaba
+fn emit_low_stock_alert(app_handle: &AppHandle, was_low: bool, item: &InventoryItem) {
+    if was_low || !is_low_stock(item.cantidad_disponible, item.cantidad_necesaria, item.punto_reorden, low_stock_percent(app_handle)) {
+        return;
+    }
+    if let Err(e) = app_handle.emit("low-stock-alert", item) {
+        eprintln!("No se pudo emitir low-stock-alert: {}", e);
+    }
+}
+
+// Inserta un artículo y su movimiento inicial. Las imágenes guardadas se
+// anotan en saved_files para poder borrarlas si la operación se revierte.
+fn insert_item(
+    db: &Connection,
+    item: &NewItem,
+    app_handle: &AppHandle,
+    now: &str,
+    saved_files: &mut Vec<String>,
+) -> Result<i64, AppError> {
+    let saved = match &item.image_base64 {
+        Some(base64_data) => Some(save_image(base64_data, app_handle)?),
+        None => None,
+    };
+    if let Some(saved) = &saved {
+        saved_files.extend(saved.files());
+    }
+
+    create_item(db, item, saved.as_ref(), now)
+}
+
+// Parte de insert_item que solo toca la base de datos; la imagen llega ya
+// guardada y image_base64 se ignora
+fn create_item(db: &Connection, item: &NewItem, saved: Option<&SavedImage>, now: &str) -> Result<i64, AppError> {
+    let name = validate_name(&item.name)?;
+    validate_quantities(item.cantidad_necesaria, item.cantidad_disponible)?;
+    validate_capacity(item.cantidad_disponible, item.capacidad_maxima)?;
+
+    let cantidad_necesaria = round_quantity(item.cantidad_necesaria);
+    let cantidad_disponible = round_quantity(item.cantidad_disponible);
+    let category = normalize_optional_text(item.category.clone());
+    let descripcion = normalize_optional_text(item.descripcion.clone());
+    let codigo_barras = normalize_optional_text(item.codigo_barras.clone());
+    let unidad = normalize_optional_text(item.unidad.clone());
+    let created_at = match &item.created_at {
+        Some(value) => parse_timestamp(value)
+            .ok_or_else(|| AppError::InvalidInput(format!("La fecha de creación no es válida: \"{}\"", value)))?,
+        None => now.to_string(),
+    };
+
+    if !item.allow_duplicate {
+        ensure_unique_name(db, &name, None)?;
+    }
+    ensure_unique_barcode(db, codigo_barras.as_deref(), None)?;
+    ensure_supplier_exists(db, item.supplier_id)?;
+
+    db.execute(
+        "INSERT INTO inventory (name, image_path, cantidad_necesaria, cantidad_disponible, created_at, updated_at, category,
+             thumbnail_path, name_normalized, precio_unitario, costo_unitario, supplier_id, descripcion, codigo_barras, unidad,
+             punto_reorden, capacidad_maxima, image_source_mime)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)",
+        params![
+            name,
+            saved.map(|s| s.image_path.clone()),
+            cantidad_necesaria,
+            cantidad_disponible,
+            created_at,
+            category,
+            saved.and_then(|s| s.thumbnail_path.clone()),
+            normalize_name(&name),
+            item.precio_unitario,
+            item.costo_unitario,
+            item.supplier_id,
+            descripcion,
+            codigo_barras,
+            unidad,
+            item.punto_reorden.map(round_quantity),
+            item.capacidad_maxima.map(round_quantity),
+            saved.map(|s| s.source_mime)
+        ],
+    )?;
+
+    let id = db.last_insert_rowid();
+
+    if let Some(saved) = saved {
+        store_image_blob(db, id, saved)?;
+    }
+
+    record_movement(db, id, 0.0, cantidad_disponible, item.reason.as_deref(), now)?;
+
+    Ok(id)
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn add_item(
+    name: String,
+    image_base64: Option<String>,
+    cantidad_necesaria: f64,
+    cantidad_disponible: f64,
+    category: Option<String>,
+    precio_unitario: Option<f64>,
+    costo_unitario: Option<f64>,
+    supplier_id: Option<i64>,
+    descripcion: Option<String>,
+    codigo_barras: Option<String>,
+    unidad: Option<String>,
+    punto_reorden: Option<f64>,
+    capacidad_maxima: Option<f64>,
+    created_at: Option<String>,
+    reason: Option<String>,
+    allow_duplicate: bool,
+    state: State<AppState>
+) -> Result<InventoryItem, AppError> {
+    let item = NewItem {
+        name,
+        image_base64,
+        cantidad_necesaria,
+        cantidad_disponible,
+        category,
+        precio_unitario,
+        costo_unitario,
+        supplier_id,
+        descripcion,
+        codigo_barras,
+        unidad,
+        punto_reorden,
+        capacidad_maxima,
+        created_at,
+        reason,
+        allow_duplicate,
+    };
+
+    // En una sola transacción: si la base de datos está ocupada a mitad del
+    // guardado no queda la fila sin su historial y el reintento no la duplica
+    // Solo la inserción va dentro del reintento: repetir lo de después
+    // insertaría otra copia
+    let id = retry_on_busy(|| {
+        let mut db = state.write_conn()?;
+
+        let now = now_timestamp();
+
+        let mut saved_files = Vec::new();
+        let inserted = with_transaction(&mut db, |tx| insert_item(tx, &item, &state.app_handle, &now, &mut saved_files));
+        if inserted.is_err() {
+            remove_image_files(&saved_files);
+        }
+        inserted
+    })?;
+
+    let db = state.conn()?;
+    let item = fetch_item(&db, id)?;
+    emit_inventory_changed(&state.app_handle, id, "add");
+
+    Ok(item)
+}
+
+// Inserta todos los artículos en una sola transacción; si alguno falla no se
+// guarda ninguno. Devuelve los ids en el mismo orden recibido.
+#[tauri::command]
+fn add_items(items: Vec<NewItem>, state: State<AppState>) -> Result<Vec<i64>, AppError> {
+    let mut db = state.write_conn()?;
+    let tx = db.transaction()?;
+
+    let now = now_timestamp();
+
+    let mut saved_files = Vec::new();
+    let mut ids = Vec::with_capacity(items.len());
+
+    for item in &items {
+        match insert_item(&tx, item, &state.app_handle, &now, &mut saved_files) {
+            Ok(id) => ids.push(id),
+            Err(e) => {
+                // Al soltar tx sin commit la transacción se revierte
+                remove_image_files(&saved_files);
+                return Err(e);
+            }
+        }
+    }
+
+    if let Err(e) = tx.commit() {
+        remove_image_files(&saved_files);
+        return Err(e.into());
+    }
+
+    for id in &ids {
+        emit_inventory_changed(&state.app_handle, *id, "add");
+    }
+
+    Ok(ids)
+}
+
+// Cantidades anteriores y archivos de la imagen reemplazada, que se borran
+// solo después de confirmar
+#[derive(Debug)]
+struct UpdateOutcome {
+    previous_qty: f64,
+    previous_needed: f64,
+    previous_reorder: Option<f64>,
+    old_files: Vec<String>,
+}
+
+// Parte de update_item que solo toca la base de datos. Se usa NewItem para los
+// campos editables; created_at e image_base64 se ignoran y la imagen nueva, si
+// la hay, llega ya guardada.
+fn apply_item_update(
+    db: &Connection,
+    id: i64,
+    item: &NewItem,
+    expected_version: Option<i64>,
+    saved: Option<&SavedImage>,
+) -> Result<UpdateOutcome, AppError> {
+    let name = validate_name(&item.name)?;
+    validate_quantities(item.cantidad_necesaria, item.cantidad_disponible)?;
+    validate_capacity(item.cantidad_disponible, item.capacidad_maxima)?;
+
+    let cantidad_necesaria = round_quantity(item.cantidad_necesaria);
+    let cantidad_disponible = round_quantity(item.cantidad_disponible);
+    let category = normalize_optional_text(item.category.clone());
+    let descripcion = normalize_optional_text(item.descripcion.clone());
+    let codigo_barras = normalize_optional_text(item.codigo_barras.clone());
+    let unidad = normalize_optional_text(item.unidad.clone());
+
+    if !item.allow_duplicate {
+        ensure_unique_name(db, &name, Some(id))?;
+    }
+    ensure_unique_barcode(db, codigo_barras.as_deref(), Some(id))?;
+    ensure_supplier_exists(db, item.supplier_id)?;
+
+    // Comprobar que el artículo existe y no cambió
+    let (previous_qty, previous_needed, previous_reorder, current_version): (f64, f64, Option<f64>, i64) = db
+        .query_row(
+            "SELECT cantidad_disponible, cantidad_necesaria, punto_reorden, version FROM inventory
+             WHERE id = ?1 AND deleted_at IS NULL",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .optional()?
+        .ok_or(AppError::NotFound(id))?;
+
+    // Sin versión esperada se sobrescribe como antes
+    if expected_version.is_some_and(|v| v != current_version) {
+        return Err(AppError::VersionConflict(id));
+    }
+
+    let mut old_files = match saved {
+        Some(_) => item_image_files(db, id)?,
+        None => Vec::new(),
+    };
+    let image_path = saved.map(|s| s.image_path.clone());
+    let thumbnail_path = saved.and_then(|s| s.thumbnail_path.clone());
+
+    // Subir la misma foto otra vez reutiliza el mismo archivo
+    old_files.retain(|path| Some(path) != image_path.as_ref() && Some(path) != thumbnail_path.as_ref());
+
+    let now = now_timestamp();
+
+    // Sin imagen nueva se conservan la imagen y la miniatura actuales
+    let updated = db.execute(
+        "UPDATE inventory SET name = ?1, cantidad_necesaria = ?2, cantidad_disponible = ?3, updated_at = ?4, category = ?5,
+             name_normalized = ?6, precio_unitario = ?7, costo_unitario = ?8, supplier_id = ?9,
+             image_path = COALESCE(?10, image_path),
+             thumbnail_path = CASE WHEN ?10 IS NULL THEN thumbnail_path ELSE ?11 END,
+             image_source_mime = CASE WHEN ?10 IS NULL THEN image_source_mime ELSE ?19 END,
+             descripcion = ?12, codigo_barras = ?13, unidad = ?14, punto_reorden = ?15, capacidad_maxima = ?16,
+             version = version + 1
+         WHERE id = ?17 AND version = ?18 AND deleted_at IS NULL",
+        params![
+            name,
+            cantidad_necesaria,
+            cantidad_disponible,
+            now,
+            category,
+            normalize_name(&name),
+            item.precio_unitario,
+            item.costo_unitario,
+            item.supplier_id,
+            image_path,
+            thumbnail_path,
+            descripcion,
+            codigo_barras,
+            unidad,
+            item.punto_reorden.map(round_quantity),
+            item.capacidad_maxima.map(round_quantity),
+            id,
+            current_version,
+            saved.map(|s| s.source_mime)
+        ],
+    )?;
+
+    // Otro dispositivo con la misma base de datos pudo escribir entretanto
+    if updated == 0 {
+        return Err(AppError::VersionConflict(id));
+    }
+
+    if let Some(saved) = saved {
+        store_image_blob(db, id, saved)?;
+    }
+
+    record_movement(db, id, previous_qty, cantidad_disponible, item.reason.as_deref(), &now)?;
+
+    Ok(UpdateOutcome {
+        previous_qty,
+        previous_needed,
+        previous_reorder,
+        old_files,
+    })
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn update_item(
+    id: i64,
+    name: String,
+    image_base64: Option<String>,
+    cantidad_necesaria: f64,
+    cantidad_disponible: f64,
+    category: Option<String>,
+    precio_unitario: Option<f64>,
+    costo_unitario: Option<f64>,
+    supplier_id: Option<i64>,
+    descripcion: Option<String>,
+    codigo_barras: Option<String>,
+    unidad: Option<String>,
+    punto_reorden: Option<f64>,
+    capacidad_maxima: Option<f64>,
+    reason: Option<String>,
+    allow_duplicate: bool,
+    expected_version: Option<i64>,
+    state: State<AppState>,
+) -> Result<InventoryItem, AppError> {
+    let item = NewItem {
+        name,
+        image_base64,
+        cantidad_necesaria,
+        cantidad_disponible,
+        category,
+        precio_unitario,
+        costo_unitario,
+        supplier_id,
+        descripcion,
+        codigo_barras,
+        unidad,
+        punto_reorden,
+        capacidad_maxima,
+        created_at: None,
+        reason,
+        allow_duplicate,
+    };
+
+    // Cada intento empieza de cero: la transacción fallida ya se revirtió
+    retry_on_busy(|| {
+        let mut db = state.write_conn()?;
+
+        let saved = match &item.image_base64 {
+            Some(base64_data) => Some(save_image(base64_data, &state.app_handle)?),
+            None => None,
+        };
+        // Archivos escritos para la imagen nueva, por si hay que deshacer
+        let created_files = saved.as_ref().map(SavedImage::files).unwrap_or_default();
+
+        // Las comprobaciones, el UPDATE y el movimiento se confirman juntos
+        let result = with_transaction(&mut db, |tx| apply_item_update(tx, id, &item, expected_version, saved.as_ref()));
+
+        let outcome = match result {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                remove_image_files(&created_files);
+                return Err(e);
+            }
+        };
+        remove_image_files(&outcome.old_files);
+
+        let updated = fetch_item(&db, id)?;
+        emit_inventory_changed(&state.app_handle, id, "update");
+        emit_low_stock_alert(
+            &state.app_handle,
+            is_low_stock(
+                outcome.previous_qty,
+                outcome.previous_needed,
+                outcome.previous_reorder,
+                low_stock_percent(&state.app_handle),
+            ),
+            &updated,
+        );
+
+        Ok(updated)
+    })
+}
+
+#[tauri::command]
+fn adjust_quantity(
+    id: i64,
+    delta: f64,
+    reason: Option<String>,
+    state: State<AppState>,
+) -> Result<InventoryItem, AppError> {
+    validate_quantity(delta)?;
+    let delta = round_quantity(delta);
+
+    let mut db = state.write_conn()?;
+
+    let now = now_timestamp();
+
+    let (item, previous_qty) =
+        with_transaction(&mut db, |tx| apply_quantity_delta(tx, id, delta, reason.as_deref(), &now))?;
+    drop(db);
+
+    emit_inventory_changed(&state.app_handle, id, "adjust");
+    emit_low_stock_alert(
+        &state.app_handle,
+        is_low_stock(previous_qty, item.cantidad_necesaria, item.punto_reorden, low_stock_percent(&state.app_handle)),
+        &item,
+    );
+
+    Ok(item)
+}
+
+// Parte de adjust_quantity que solo toca la base de datos; delta ya viene
+// redondeado. Devuelve el artículo y la cantidad que tenía antes.
+fn apply_quantity_delta(
+    db: &Connection,
+    id: i64,
+    delta: f64,
+    reason: Option<&str>,
+    now: &str,
+) -> Result<(InventoryItem, f64), AppError> {
+    // La condición evita escribir un valor negativo o por encima de la
+    // capacidad en la misma sentencia; la suma se redondea igual que en Rust
+    let updated = db.execute(
+        "UPDATE inventory SET cantidad_disponible = ROUND(cantidad_disponible + ?1, ?4), updated_at = ?2,
+             version = version + 1
+         WHERE id = ?3 AND deleted_at IS NULL AND cantidad_disponible + ?1 > -?5
+           AND (capacidad_maxima IS NULL OR cantidad_disponible + ?1 <= capacidad_maxima + ?5)",
+        params![delta, now, id, QUANTITY_DECIMALS, QUANTITY_EPSILON],
+    )?;
+
+    if updated == 0 {
+        let current: Option<(f64, Option<f64>)> = db
+            .query_row(
+                "SELECT cantidad_disponible, capacidad_maxima FROM inventory WHERE id = ?1 AND deleted_at IS NULL",
+                [id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        let Some((cantidad_disponible, capacidad_maxima)) = current else {
+            return Err(AppError::NotFound(id));
+        };
+        validate_capacity(cantidad_disponible + delta, capacidad_maxima)?;
+        return Err(AppError::InvalidInput(
+            "La cantidad disponible no puede quedar por debajo de cero".to_string(),
+        ));
+    }
+
+    let item = fetch_item(db, id)?;
+    let previous_qty = round_quantity(item.cantidad_disponible - delta);
+
+    record_movement(db, id, previous_qty, item.cantidad_disponible, reason, now)?;
+
+    Ok((item, previous_qty))
+}
+
+// Descuenta varias cantidades a la vez (por ejemplo en una venta): si a un
+// artículo no le alcanza la existencia no se descuenta ninguno
+#[tauri::command]
+fn consume_stock(items: Vec<(i64, f64)>, state: State<AppState>) -> Result<Vec<InventoryItem>, AppError> {
+    for (_, quantity) in &items {
+        validate_quantity(*quantity)?;
+    }
+    let items: Vec<(i64, f64)> = items.into_iter().map(|(id, quantity)| (id, round_quantity(quantity))).collect();
+    if items.iter().any(|(_, quantity)| *quantity <= 0.0) {
+        return Err(AppError::InvalidInput("Las cantidades a descontar deben ser mayores que cero".to_string()));
+    }
+
+    let percent = low_stock_percent(&state.app_handle);
+    let mut db = state.write_conn()?;
+    let now = now_timestamp();
+
+    let changed = with_transaction(&mut db, |tx| {
+        let mut changed = Vec::new();
+        for (id, quantity) in &items {
+            let item = fetch_item(tx, *id)?;
+            if item.cantidad_disponible < *quantity - QUANTITY_EPSILON {
+                return Err(AppError::InvalidInput(format!(
+                    "No hay suficiente \"{}\" (id {}): disponible {}, solicitado {}",
+                    item.name, id, item.cantidad_disponible, quantity
+                )));
+            }
+
+            let new_qty = round_quantity(item.cantidad_disponible - quantity).max(0.0);
+            tx.execute(
+                "UPDATE inventory SET cantidad_disponible = ?1, updated_at = ?2, version = version + 1
+                 WHERE id = ?3 AND deleted_at IS NULL",
+                params![new_qty, now, id],
+            )?;
+            record_movement(tx, *id, item.cantidad_disponible, new_qty, Some("Venta"), &now)?;
+
+            changed.push((*id, is_low_stock(item.cantidad_disponible, item.cantidad_necesaria, item.punto_reorden, percent)));
+        }
+        Ok(changed)
+    })?;
+
+    // Un mismo artículo puede venir varias veces; se devuelve una sola vez
+    let mut updated: Vec<InventoryItem> = Vec::new();
+    for (id, was_low) in changed {
+        if updated.iter().any(|item| item.id == Some(id)) {
+            continue;
+        }
+        let item = fetch_item(&db, id)?;
+        emit_inventory_changed(&state.app_handle, id, "adjust");
+        emit_low_stock_alert(&state.app_handle, was_low, &item);
+        updated.push(item);
+    }
+
+    Ok(updated)
+}
+
+fn open_count_session(db: &Connection) -> Result<Option<i64>> {
+    db.query_row(
+        "SELECT id FROM count_sessions WHERE finished_at IS NULL ORDER BY id DESC LIMIT 1",
+        [],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+// Inicia un conteo físico: guarda las cantidades actuales y, si se pide, deja
+// todas las existencias en cero para volver a capturarlas con apply_count
+#[tauri::command]
+fn begin_physical_count(zero_quantities: bool, state: State<AppState>) -> Result<CountSession, AppError> {
+    let mut db = state.write_conn()?;
+    let now = now_timestamp();
+
+    let session_id = with_transaction(&mut db, |tx| {
+        if open_count_session(tx)?.is_some() {
+            return Err(AppError::InvalidInput("Ya hay un conteo físico en curso".to_string()));
+        }
+
+        tx.execute(
+            "INSERT INTO count_sessions (started_at, zeroed) VALUES (?1, ?2)",
+            params![now, zero_quantities],
+        )?;
+        let session_id = tx.last_insert_rowid();
+
+        tx.execute(
+            "INSERT INTO count_lines (session_id, item_id, item_name, snapshot_qty)
+             SELECT ?1, id, name, cantidad_disponible FROM inventory WHERE deleted_at IS NULL",
+            [session_id],
+        )?;
+
+        if zero_quantities {
+            let items: Vec<(i64, f64)> = tx
+                .prepare("SELECT id, cantidad_disponible FROM inventory WHERE deleted_at IS NULL AND cantidad_disponible != 0")?
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<Result<Vec<_>, _>>()?;
+            for (id, previous_qty) in items {
+                tx.execute(
+                    "UPDATE inventory SET cantidad_disponible = 0, updated_at = ?1, version = version + 1 WHERE id = ?2",
+                    params![now, id],
+                )?;
+                record_movement(tx, id, previous_qty, 0.0, Some("Inicio de conteo físico"), &now)?;
+            }
+        }
+
+        Ok(session_id)
+    })?;
+
+    Ok(CountSession {
+        id: session_id,
+        started_at: now,
+        finished_at: None,
+        zeroed: zero_quantities,
+    })
+}
+
+// Captura la cantidad contada de un artículo y la deja como existencia actual
+#[tauri::command]
+fn apply_count(id: i64, actual: f64, state: State<AppState>) -> Result<InventoryItem, AppError> {
+    validate_quantity(actual)?;
+    if actual < 0.0 {
+        return Err(AppError::InvalidInput("La cantidad contada no puede ser negativa".to_string()));
+    }
+    let actual = round_quantity(actual);
+
+    let percent = low_stock_percent(&state.app_handle);
+    let mut db = state.write_conn()?;
+    let now = now_timestamp();
+
+    let was_low = with_transaction(&mut db, |tx| {
+        let session_id = open_count_session(tx)?
+            .ok_or_else(|| AppError::InvalidInput("No hay un conteo físico en curso".to_string()))?;
+        let item = fetch_item(tx, id)?;
+        validate_capacity(actual, item.capacidad_maxima)?;
+
+        let updated = tx.execute(
+            "UPDATE count_lines SET counted_qty = ?1 WHERE session_id = ?2 AND item_id = ?3",
+            params![actual, session_id, id],
+        )?;
+        if updated == 0 {
+            return Err(AppError::InvalidInput(format!(
+                "\"{}\" no estaba en el inventario al iniciar el conteo",
+                item.name
+            )));
+        }
+
+        tx.execute(
+            "UPDATE inventory SET cantidad_disponible = ?1, updated_at = ?2, version = version + 1 WHERE id = ?3",
+            params![actual, now, id],
+        )?;
+        record_movement(tx, id, item.cantidad_disponible, actual, Some("Conteo físico"), &now)?;
+
+        Ok(is_low_stock(item.cantidad_disponible, item.cantidad_necesaria, item.punto_reorden, percent))
+    })?;
+
+    let item = fetch_item(&db, id)?;
+    emit_inventory_changed(&state.app_handle, id, "adjust");
+    emit_low_stock_alert(&state.app_handle, was_low, &item);
+
+    Ok(item)
+}
+
+// Cierra el conteo y guarda la diferencia entre lo que había al empezar y la
+// cantidad final de cada artículo. Devuelve las líneas con diferencia primero.
+#[tauri::command]
+fn finish_count(state: State<AppState>) -> Result<Vec<CountLine>, AppError> {
+    let mut db = state.write_conn()?;
+    let now = now_timestamp();
+
+    with_transaction(&mut db, |tx| {
+        let session_id = open_count_session(tx)?
+            .ok_or_else(|| AppError::InvalidInput("No hay un conteo físico en curso".to_string()))?;
+
+        // Un artículo eliminado durante el conteo conserva lo último capturado
+        tx.execute(
+            "UPDATE count_lines SET final_qty = COALESCE(
+                 (SELECT cantidad_disponible FROM inventory WHERE id = count_lines.item_id),
+                 counted_qty, snapshot_qty)
+             WHERE session_id = ?1",
+            [session_id],
+        )?;
+        tx.execute(
+            "UPDATE count_lines SET variance = ROUND(final_qty - snapshot_qty, ?2) WHERE session_id = ?1",
+            params![session_id, QUANTITY_DECIMALS],
+        )?;
+        tx.execute(
+            "UPDATE count_sessions SET finished_at = ?1 WHERE id = ?2",
+            params![now, session_id],
+        )?;
+
+        let mut stmt = tx.prepare(
+            "SELECT item_id, item_name, snapshot_qty, counted_qty, final_qty, variance FROM count_lines
+             WHERE session_id = ?1 ORDER BY ABS(variance) DESC, item_name COLLATE NOCASE, item_id",
+        )?;
+        let lines = stmt
+            .query_map([session_id], |row| {
+                Ok(CountLine {
+                    item_id: row.get(0)?,
+                    item_name: row.get(1)?,
+                    snapshot_qty: row.get(2)?,
+                    counted_qty: row.get(3)?,
+                    final_qty: row.get(4)?,
+                    variance: row.get(5)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(lines)
+    })
+}
+
+// Cambia la cantidad necesaria de varios artículos a la vez (por ejemplo al
+// cambiar de temporada). Los ids que no existen se ignoran.
+#[tauri::command]
+fn set_needed_quantities(updates: Vec<(i64, f64)>, state: State<AppState>) -> Result<usize, AppError> {
+    for (_, value) in &updates {
+        validate_quantity(*value)?;
+    }
+    if updates.iter().any(|(_, value)| *value < 0.0) {
+        return Err(AppError::InvalidInput("La cantidad necesaria no puede ser negativa".to_string()));
+    }
+
+    let percent = low_stock_percent(&state.app_handle);
+    let mut db = state.write_conn()?;
+    let tx = db.transaction()?;
+    let now = now_timestamp();
+
+    let mut changed = Vec::new();
+    for (id, cantidad_necesaria) in updates {
+        let Some(before) = tx
+            .query_row(
+                "SELECT cantidad_disponible, cantidad_necesaria, punto_reorden FROM inventory
+                 WHERE id = ?1 AND deleted_at IS NULL",
+                [id],
+                |row| Ok((row.get::<_, f64>(0)?, row.get::<_, f64>(1)?, row.get::<_, Option<f64>>(2)?)),
+            )
+            .optional()?
+        else {
+            continue;
+        };
+
+        tx.execute(
+            "UPDATE inventory SET cantidad_necesaria = ?1, updated_at = ?2, version = version + 1 WHERE id = ?3",
+            params![round_quantity(cantidad_necesaria), now, id],
+        )?;
+        changed.push((id, is_low_stock(before.0, before.1, before.2, percent)));
+    }
+
+    tx.commit()?;
+
+    for (id, was_low) in &changed {
+        emit_inventory_changed(&state.app_handle, *id, "update");
+        emit_low_stock_alert(&state.app_handle, *was_low, &fetch_item(&db, *id)?);
+    }
+
+    Ok(changed.len())
+}
+
+// Ejecuta f dentro de una transacción: confirma si devuelve Ok y, si devuelve
+// Err, la transacción se descarta sin confirmar y se revierte todo
+fn with_transaction<T>(
+    conn: &mut Connection,
+    f: impl FnOnce(&rusqlite::Transaction) -> Result<T, AppError>,
+) -> Result<T, AppError> {
+    let tx = conn.transaction()?;
+    let value = f(&tx)?;
+    tx.commit()?;
+    Ok(value)
+}
+
+// Registrar un cambio de cantidad disponible; si no hubo cambio no se guarda nada
+fn record_movement(
+    db: &Connection,
+    item_id: i64,
+    previous_qty: f64,
+    new_qty: f64,
+    reason: Option<&str>,
+    timestamp: &str,
+) -> Result<()> {
+    let delta = round_quantity(new_qty - previous_qty);
+    if delta.abs() < QUANTITY_EPSILON {
+        return Ok(());
+    }
+
+    db.execute(
+        "INSERT INTO stock_movements (item_id, delta, reason, previous_qty, new_qty, timestamp) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![item_id, delta, reason, previous_qty, new_qty, timestamp],
+    )?;
+
+    Ok(())
+}
+
+#[tauri::command]
+fn get_item_history(item_id: i64, state: State<AppState>) -> Result<Vec<StockMovement>, AppError> {
+    let db = state.conn()?;
+    let mut stmt = db.prepare(&format!(
+        "SELECT {} FROM stock_movements WHERE item_id = ?1 ORDER BY timestamp DESC, id DESC",
+        MOVEMENT_COLUMNS
+    ))?;
+
+    let movements = stmt
+        .query_map([item_id], movement_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(movements)
+}
+
+// Bitácora de movimientos de un periodo para auditoría, en orden cronológico;
+// ambos extremos se incluyen. Con item_id solo los de ese artículo, incluidos
+// los que están en la papelera.
+#[tauri::command]
+fn get_movements(
+    start: String,
+    end: String,
+    item_id: Option<i64>,
+    state: State<AppState>,
+) -> Result<Vec<StockMovement>, AppError> {
+    let (start_ts, end_ts) = parse_date_range(&start, &end)?;
+
+    let db = state.conn()?;
+    let mut stmt = db.prepare(&format!(
+        "SELECT {} FROM stock_movements
+         WHERE timestamp BETWEEN ?1 AND ?2 AND (?3 IS NULL OR item_id = ?3)
+         ORDER BY timestamp ASC, id ASC",
+        MOVEMENT_COLUMNS
+    ))?;
+
+    let movements = stmt
+        .query_map(params![start_ts, end_ts, item_id], movement_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(movements)
+}
+
+const MOVEMENT_COLUMNS: &str = "id, item_id, delta, reason, previous_qty, new_qty, timestamp";
+
+fn movement_from_row(row: &rusqlite::Row) -> Result<StockMovement> {
+    Ok(StockMovement {
+        id: row.get(0)?,
+        item_id: row.get(1)?,
+        delta: row.get(2)?,
+        reason: row.get(3)?,
+        previous_qty: row.get(4)?,
+        new_qty: row.get(5)?,
+        timestamp: row.get(6)?,
+    })
+}
+
+// Últimos movimientos de todo el inventario
+fn query_recent_movements(db: &Connection, limit: i64) -> Result<Vec<StockMovement>> {
+    let mut stmt = db.prepare(&format!(
+        "SELECT {} FROM stock_movements ORDER BY timestamp DESC, id DESC LIMIT ?1",
+        MOVEMENT_COLUMNS
+    ))?;
+
+    let movements = stmt
+        .query_map([limit], movement_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(movements)
+}
+
+// Rutas de la imagen y la miniatura de un artículo que ningún otro artículo
+// usa (incluidos los de la papelera), es decir, las que se pueden borrar
+fn item_image_files(db: &Connection, id: i64) -> Result<Vec<String>> {
+    let paths = db
+        .query_row(
+            "SELECT image_path, thumbnail_path FROM inventory WHERE id = ?1",
+            [id],
+            |row| Ok((row.get::<_, Option<String>>(0)?, row.get::<_, Option<String>>(1)?)),
+        )
+        .optional()?;
+
+    let Some((image_path, thumbnail_path)) = paths else {
+        return Ok(Vec::new());
+    };
+
+    let mut files = Vec::new();
+    for path in [image_path, thumbnail_path].into_iter().flatten() {
+        if path == DB_IMAGE_MARKER {
+            continue;
+        }
+        let shared: bool = db.query_row(
+            "SELECT EXISTS(SELECT 1 FROM inventory WHERE id != ?1 AND (image_path = ?2 OR thumbnail_path = ?2))",
+            params![id, path],
+            |row| row.get(0),
+        )?;
+        if !shared {
+            files.push(path);
+        }
+    }
+
+    Ok(files)
+}
+
+// Copia la imagen y la miniatura de un artículo para otro. El nombre lleva el
+// id del artículo nuevo para no coincidir con el del original; la imagen
+// guardada en la base de datos se copia aparte.
+fn copy_item_image_files(
+    source: &InventoryItem,
+    new_id: i64,
+    created_files: &mut Vec<String>,
+) -> Result<(Option<String>, Option<String>), AppError> {
+    let mut copy = |path: Option<&String>| -> Result<Option<String>, AppError> {
+        let Some(path) = path.filter(|path| *path != DB_IMAGE_MARKER) else {
+            return Ok(path.cloned());
+        };
+        let source_path = Path::new(path);
+        let stem = source_path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("imagen");
+        let file_name = match source_path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) => format!("{}-{}.{}", stem, new_id, ext),
+            None => format!("{}-{}", stem, new_id),
+        };
+        let target = source_path.with_file_name(file_name);
+        fs::copy(source_path, &target)?;
+        let target = target.to_string_lossy().to_string();
+        created_files.push(target.clone());
+        Ok(Some(target))
+    };
+
+    let image_path = copy(source.image_path.as_ref())?;
+    let thumbnail_path = copy(source.thumbnail_path.as_ref())?;
+    Ok((image_path, thumbnail_path))
+}
+
+// Un archivo que no se puede borrar no debe deshacer la operación en la base
+// de datos, solo se registra
+fn remove_image_files(paths: &[String]) {
+    for path in paths {
+        if let Err(e) = fs::remove_file(path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                eprintln!("No se pudo eliminar la imagen {}: {}", path, e);
+            }
+        }
+    }
+}
+
+#[tauri::command]
+fn clear_item_image(id: i64, state: State<AppState>) -> Result<InventoryItem, AppError> {
+    let mut db = state.write_conn()?;
+
+    let image_files = with_transaction(&mut db, |tx| clear_image_row(tx, id))?;
+
+    // Los archivos se borran solo después de confirmar; si ya no existen no es
+    // un error
+    remove_image_files(&image_files);
+
+    let item = fetch_item(&db, id)?;
+    emit_inventory_changed(&state.app_handle, id, "update");
+
+    Ok(item)
+}
+
+// Parte de clear_item_image que solo toca la base de datos; devuelve los
+// archivos que quedaron sin usar
+fn clear_image_row(db: &Connection, id: i64) -> Result<Vec<String>, AppError> {
+    let image_files = item_image_files(db, id)?;
+
+    let updated = db.execute(
+        "UPDATE inventory SET image_path = NULL, thumbnail_path = NULL, image_source_mime = NULL, updated_at = ?1,
+             version = version + 1
+         WHERE id = ?2 AND deleted_at IS NULL",
+        params![now_timestamp(), id],
+    )?;
+    if updated == 0 {
+        return Err(AppError::NotFound(id));
+    }
+    db.execute("DELETE FROM item_images WHERE item_id = ?1", [id])?;
+
+    Ok(image_files)
+}
+
+#[tauri::command]
+fn cleanup_orphaned_images(dry_run: bool, state: State<AppState>) -> Result<CleanupReport, AppError> {
+    let db = state.write_conn()?;
+    let images_dir = get_images_dir(&state.app_handle)?;
+
+    remove_orphaned_images(&db, &images_dir, dry_run)
+}
+
+// Con dry_run solo se reportan los archivos que ningún artículo usa
+fn remove_orphaned_images(db: &Connection, images_dir: &Path, dry_run: bool) -> Result<CleanupReport, AppError> {
+    // Nombres de archivo referenciados por algún artículo, incluidos los de la papelera
+    let mut stmt = db.prepare("SELECT image_path, thumbnail_path FROM inventory")?;
+    let referenced: std::collections::HashSet<std::ffi::OsString> = stmt
+        .query_map([], |row| Ok((row.get::<_, Option<String>>(0)?, row.get::<_, Option<String>>(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .flat_map(|(image_path, thumbnail_path)| [image_path, thumbnail_path])
+        .flatten()
+        .filter_map(|path| Path::new(&path).file_name().map(|name| name.to_os_string()))
+        .collect();
+
+    let mut report = CleanupReport {
+        deleted_files: 0,
+        bytes_freed: 0,
+        files: Vec::new(),
+        dry_run,
+    };
+
+    for dir in [images_dir.to_path_buf(), images_dir.join("thumbnails")] {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if !metadata.is_file() || referenced.contains(&entry.file_name()) {
+                continue;
+            }
+
+            if !dry_run {
+                if let Err(e) = fs::remove_file(&path) {
+                    eprintln!("No se pudo eliminar la imagen {}: {}", path.display(), e);
+                    continue;
+                }
+            }
+
+            report.deleted_files += 1;
+            report.bytes_freed += metadata.len();
+            report.files.push(path.to_string_lossy().to_string());
+        }
+    }
+
+    Ok(report)
+}
+
+// Reconcilia las rutas con los archivos en ambos sentidos: quita las
+// referencias a imágenes que ya no existen y reporta, sin borrarlos, los
+// archivos que ningún artículo usa (eso queda para cleanup_orphaned_images).
+// A diferencia de fix_image_paths no busca los archivos en otra carpeta.
+#[tauri::command]
+fn repair_image_links(state: State<AppState>) -> Result<RepairReport, AppError> {
+    let mut db = state.write_conn()?;
+
+    let mut stmt = db.prepare(
+        "SELECT id, image_path, thumbnail_path, EXISTS(SELECT 1 FROM item_images WHERE item_id = inventory.id)
+         FROM inventory WHERE image_path IS NOT NULL OR thumbnail_path IS NOT NULL",
+    )?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, bool>(3)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    drop(stmt);
+
+    let dangling = |path: &str, has_blob: bool| {
+        if path == DB_IMAGE_MARKER {
+            !has_blob
+        } else {
+            !Path::new(path).exists()
+        }
+    };
+
+    let now = now_timestamp();
+    let mut cleared_images = 0;
+    let mut cleared_thumbnails = 0;
+    let mut repaired_ids = Vec::new();
+
+    with_transaction(&mut db, |tx| {
+        for (id, image_path, thumbnail_path, has_blob) in &rows {
+            let clear_image = image_path.as_deref().is_some_and(|path| dangling(path, *has_blob));
+            let clear_thumbnail = thumbnail_path.as_deref().is_some_and(|path| dangling(path, *has_blob));
+            if !clear_image && !clear_thumbnail {
+                continue;
+            }
+
+            // Sin imagen tampoco se conserva la miniatura
+            tx.execute(
+                "UPDATE inventory SET image_path = CASE WHEN ?1 THEN NULL ELSE image_path END,
+                     thumbnail_path = CASE WHEN ?1 OR ?2 THEN NULL ELSE thumbnail_path END,
+                     image_source_mime = CASE WHEN ?1 THEN NULL ELSE image_source_mime END,
+                     updated_at = ?3, version = version + 1
+                 WHERE id = ?4",
+                params![clear_image, clear_thumbnail, now, id],
+            )?;
+            if clear_image {
+                tx.execute("DELETE FROM item_images WHERE item_id = ?1", [id])?;
+                cleared_images += 1;
+            }
+            if thumbnail_path.is_some() {
+                cleared_thumbnails += 1;
+            }
+            repaired_ids.push(*id);
+        }
+        Ok(())
+    })?;
+
+    let images_dir = get_images_dir(&state.app_handle)?;
+    let orphans = remove_orphaned_images(&db, &images_dir, true)?;
+
+    for id in repaired_ids {
+        emit_inventory_changed(&state.app_handle, id, "update");
+    }
+
+    Ok(RepairReport {
+        cleared_images,
+        cleared_thumbnails,
+        orphaned_files: orphans.deleted_files,
+        orphaned_bytes: orphans.bytes_freed,
+        orphans: orphans.files,
+    })
+}
+
+// Todos los archivos de imágenes y miniaturas con los artículos que los usan,
+// para revisar antes de cleanup_orphaned_images
+#[tauri::command]
+fn list_images(state: State<AppState>) -> Result<Vec<ImageInfo>, AppError> {
+    let db = state.conn()?;
+
+    let mut stmt = db.prepare("SELECT id, name, image_path, thumbnail_path FROM inventory ORDER BY id")?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, Option<String>>(3)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    drop(stmt);
+    drop(db);
+
+    let mut owners: HashMap<std::ffi::OsString, Vec<ImageOwner>> = HashMap::new();
+    for (id, name, image_path, thumbnail_path) in rows {
+        for path in [image_path, thumbnail_path].into_iter().flatten() {
+            if let Some(filename) = Path::new(&path).file_name() {
+                owners.entry(filename.to_os_string()).or_default().push(ImageOwner {
+                    item_id: id,
+                    item_name: name.clone(),
+                });
+            }
+        }
+    }
+
+    let images_dir = get_images_dir(&state.app_handle)?;
+    let mut images = Vec::new();
+
+    for (dir, is_thumbnail) in [(images_dir.clone(), false), (images_dir.join("thumbnails"), true)] {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+
+        for entry in entries.flatten() {
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if !metadata.is_file() {
+                continue;
+            }
+
+            images.push(ImageInfo {
+                filename: entry.file_name().to_string_lossy().to_string(),
+                path: entry.path().to_string_lossy().to_string(),
+                size_bytes: metadata.len(),
+                is_thumbnail,
+                items: owners.get(&entry.file_name()).cloned().unwrap_or_default(),
+            });
+        }
+    }
+
+    images.sort_by(|a, b| a.is_thumbnail.cmp(&b.is_thumbnail).then_with(|| a.filename.cmp(&b.filename)));
+
+    Ok(images)
+}
+
+// Artículos sin foto, para ir completándolas. Con check_files también cuentan
+// los que tienen una ruta cuyo archivo ya no existe. limit y offset paginan
+// el resultado ya filtrado.
+#[tauri::command]
+fn get_items_without_images(
+    check_files: bool,
+    limit: Option<i64>,
+    offset: Option<i64>,
+    state: State<AppState>,
+) -> Result<Vec<InventoryItem>, AppError> {
+    let db = state.conn()?;
+    query_items_by_image(&db, false, check_files, limit, offset)
+}
+
+#[tauri::command]
+fn get_items_with_images(
+    check_files: bool,
+    limit: Option<i64>,
+    offset: Option<i64>,
+    state: State<AppState>,
+) -> Result<Vec<InventoryItem>, AppError> {
+    let db = state.conn()?;
+    query_items_by_image(&db, true, check_files, limit, offset)
+}
+
+fn query_items_by_image(
+    db: &Connection,
+    with_images: bool,
+    check_files: bool,
+    limit: Option<i64>,
+    offset: Option<i64>,
+) -> Result<Vec<InventoryItem>, AppError> {
+    // La última columna indica si la imagen está guardada en item_images
+    let mut stmt = db.prepare(&format!(
+        "SELECT {}, EXISTS(SELECT 1 FROM item_images WHERE item_id = inventory.id) FROM inventory
+         WHERE deleted_at IS NULL ORDER BY name COLLATE NOCASE, id",
+        ITEM_COLUMNS
+    ))?;
+    let blob_column = ITEM_COLUMNS.split(',').count();
+
+    let rows = stmt
+        .query_map([], |row| Ok((item_from_row(row)?, row.get::<_, bool>(blob_column)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let items = rows
+        .into_iter()
+        .filter(|(item, has_blob)| {
+            let has_image = match item.image_path.as_deref() {
+                None => false,
+                Some(_) if !check_files => true,
+                Some(DB_IMAGE_MARKER) => *has_blob,
+                Some(path) => Path::new(path).exists(),
+            };
+            has_image == with_images
+        })
+        .map(|(item, _)| item)
+        .skip(offset.unwrap_or(0).max(0) as usize)
+        .take(limit.map_or(usize::MAX, |limit| limit.max(0) as usize))
+        .collect();
+
+    Ok(items)
+}
+
+#[tauri::command]
+fn get_thumbnail(id: i64, state: State<AppState>) -> Result<Option<String>, AppError> {
+    let db = state.conn()?;
+    let paths = db
+        .query_row(
+            "SELECT thumbnail_path, image_path FROM inventory WHERE id = ?1",
+            [id],
+            |row| Ok((row.get::<_, Option<String>>(0)?, row.get::<_, Option<String>>(1)?)),
+        )
+        .optional()?
+        .ok_or(AppError::NotFound(id))?;
+
+    // Si no hay miniatura se usa la imagen completa
+    for path in [paths.0, paths.1].into_iter().flatten() {
+        if let Some(data_url) = item_image_data_url(&db, id, &path)? {
+            return Ok(Some(data_url));
+        }
+    }
+
+    Ok(None)
+}
+
+#[tauri::command]
+fn get_image(id: i64, state: State<AppState>) -> Result<Option<String>, AppError> {
+    let db = state.conn()?;
+    let image_path: Option<String> = db
+        .query_row("SELECT image_path FROM inventory WHERE id = ?1", [id], |row| row.get(0))
+        .optional()?
+        .ok_or(AppError::NotFound(id))?;
+
+    match image_path {
+        Some(path) => Ok(item_image_data_url(&db, id, &path)?),
+        None => Ok(None),
+    }
+}
+
+// La imagen de un artículo como data URL, venga de un archivo o de item_images
+fn item_image_data_url(db: &Connection, item_id: i64, path: &str) -> Result<Option<String>> {
+    use base64::{Engine as _, engine::general_purpose};
+
+    if path != DB_IMAGE_MARKER {
+        return Ok(read_image_data_url(path));
+    }
+
+    let blob = db
+        .query_row(
+            "SELECT mime, data FROM item_images WHERE item_id = ?1",
+            [item_id],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, Vec<u8>>(1)?)),
+        )
+        .optional()?;
+
+    Ok(blob.map(|(mime, data)| format!("data:{};base64,{}", mime, general_purpose::STANDARD.encode(&data))))
+}
+
+// Comprueba que el directorio de datos se pueda usar antes de la primera
+// escritura, para que la interfaz muestre el problema en lugar de fallar después
+#[tauri::command]
+fn check_storage_access(state: State<AppState>) -> Result<StorageStatus, AppError> {
+    let data_dir = get_app_data_dir(&state.app_handle)?;
+    let mut problems = Vec::new();
+
+    let exists = data_dir.is_dir();
+    if !exists {
+        problems.push(format!("El directorio {} no existe", data_dir.display()));
+    }
+
+    let probe = data_dir.join(format!(".write_test_{}", std::process::id()));
+    let writable = exists
+        && match fs::write(&probe, b"ok").and_then(|_| fs::remove_file(&probe)) {
+            Ok(()) => true,
+            Err(e) => {
+                problems.push(format!("No se puede escribir en {}: {}", data_dir.display(), e));
+                false
+            }
+        };
+
+    let images_dir = get_images_dir(&state.app_handle)?;
+    let images_dir_ok = match fs::create_dir_all(&images_dir) {
+        Ok(()) => true,
+        Err(e) => {
+            problems.push(format!("No se puede crear {}: {}", images_dir.display(), e));
+            false
+        }
+    };
+
+    let free_space_bytes = if exists {
+        match fs2::available_space(&data_dir) {
+            Ok(bytes) => Some(bytes),
+            Err(e) => {
+                problems.push(format!("No se pudo obtener el espacio libre: {}", e));
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    Ok(StorageStatus {
+        data_dir: data_dir.to_string_lossy().to_string(),
+        exists,
+        writable,
+        images_dir_ok,
+        free_space_bytes,
+        problems,
+    })
+}
+
+// Valor que se usa mientras la clave no se haya guardado
+fn default_setting(key: &str) -> Option<String> {
+    match key {
+        "locale" => Some("es_MX".to_string()),
+        "low_stock_threshold_percent" => Some(LOW_STOCK_DEFAULT_PERCENT.to_string()),
+        "max_image_bytes" => Some(MAX_IMAGE_BYTES.to_string()),
+        // "original" conserva el formato recibido; "webp" convierte PNG y JPEG
+        "image_format" => Some("original".to_string()),
+        "webp_quality" => Some("80".to_string()),
+        // 0 pausa la revisión programada del stock bajo
+        "low_stock_check_minutes" => Some(LOW_STOCK_CHECK_DEFAULT_MINUTES.to_string()),
+        _ => None,
+    }
+}
+
+// Preferencia guardada o su valor por defecto, para el código que solo recibe
+// el AppHandle
+fn app_setting(app_handle: &AppHandle, key: &str) -> Option<String> {
+    let state = app_handle.try_state::<AppState>()?;
+    let settings = state.settings.read().ok()?;
+    settings.get(key).cloned().or_else(|| default_setting(key))
+}
+
+fn validate_setting(key: &str, value: &str) -> Result<(), AppError> {
+    if key.trim().is_empty() {
+        return Err(AppError::InvalidInput("La clave de la preferencia no puede estar vacía".to_string()));
+    }
+    let valid = match key {
+        "locale" => value.parse::<chrono::Locale>().is_ok(),
+        "low_stock_threshold_percent" | "max_image_bytes" => value.parse::<u64>().is_ok_and(|v| v > 0),
+        "image_format" => matches!(value, "original" | "webp"),
+        "webp_quality" => value.parse::<u8>().is_ok_and(|v| (1..=100).contains(&v)),
+        "low_stock_check_minutes" => value.parse::<u64>().is_ok(),
+        _ => true,
+    };
+    if !valid {
+        return Err(AppError::InvalidInput(format!(
+            "Valor inválido para la preferencia {}: \"{}\"",
+            key, value
+        )));
+    }
+    Ok(())
+}
+
+fn load_settings(db: &Connection) -> Result<HashMap<String, String>> {
+    let mut stmt = db.prepare("SELECT key, value FROM settings")?;
+    let settings = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<HashMap<_, _>, _>>()?;
+    Ok(settings)
+}
+
+#[tauri::command]
+fn get_setting(key: String, state: State<AppState>) -> Result<Option<String>, AppError> {
+    let settings = state.settings.read()?;
+    Ok(settings.get(&key).cloned().or_else(|| default_setting(&key)))
+}
+
+#[tauri::command]
+fn set_setting(key: String, value: String, state: State<AppState>) -> Result<(), AppError> {
+    validate_setting(&key, &value)?;
+
+    let db = state.write_conn()?;
+    db.execute(
+        "INSERT INTO settings (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![key, value],
+    )?;
+    state.settings.write()?.insert(key, value);
+
+    Ok(())
+}
+
+// Define dónde se guardan las imágenes nuevas; las existentes no se mueven
+#[tauri::command]
+fn set_image_storage(in_database: bool, state: State<AppState>) -> Result<(), AppError> {
+    let mut settings = load_local_settings(&state.app_handle)?;
+    settings.images_in_database = in_database;
+    save_local_settings(&state.app_handle, &settings)
+}
+
+#[tauri::command]
+fn get_image_storage(state: State<AppState>) -> Result<bool, AppError> {
+    Ok(load_local_settings(&state.app_handle)?.images_in_database)
+}
+
+// Pasa las imágenes en archivos a la tabla item_images para que el inventario
+// completo viaje en el archivo .db. Los archivos originales se borran al
+// terminar; las miniaturas se conservan. Devuelve cuántas imágenes se movieron.
+#[tauri::command]
+fn migrate_images_to_database(state: State<AppState>) -> Result<usize, AppError> {
+    let mut db = state.write_conn()?;
+    let tx = db.transaction()?;
+
+    let mut stmt = tx.prepare("SELECT id, image_path FROM inventory WHERE image_path IS NOT NULL AND image_path != ?1")?;
+    let items = stmt
+        .query_map([DB_IMAGE_MARKER], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    drop(stmt);
+
+    let mut migrated_files = Vec::new();
+    for (id, path) in items {
+        // Un archivo que falta se deja como está; check_integrity lo informa
+        let Ok(data) = fs::read(&path) else {
+            continue;
+        };
+        let mime = extension_from_bytes(&data).map(mime_for_extension).unwrap_or("image/png");
+
+        tx.execute(
+            "INSERT OR REPLACE INTO item_images (item_id, mime, data) VALUES (?1, ?2, ?3)",
+            params![id, mime, data],
+        )?;
+        tx.execute(
+            "UPDATE inventory SET image_path = ?1 WHERE id = ?2",
+            params![DB_IMAGE_MARKER, id],
+        )?;
+        migrated_files.push(path);
+    }
+
+    tx.commit()?;
+
+    let mut settings = load_local_settings(&state.app_handle)?;
+    settings.images_in_database = true;
+    save_local_settings(&state.app_handle, &settings)?;
+
+    remove_image_files(&migrated_files);
+
+    Ok(migrated_files.len())
+}
+
+// Leer una imagen como data URL; None si el archivo ya no existe
+fn read_image_data_url(path: &str) -> Option<String> {
+    use base64::{Engine as _, engine::general_purpose};
+
+    let data = fs::read(path).ok()?;
+    let mime = extension_from_bytes(&data).map(mime_for_extension).unwrap_or("image/png");
+
+    Some(format!("data:{};base64,{}", mime, general_purpose::STANDARD.encode(&data)))
+}
+
+// Enviar a la papelera; la imagen se conserva para poder restaurarlo
+fn soft_delete_item(db: &Connection, id: i64, now: &str) -> Result<(), AppError> {
+    let deleted = db.execute(
+        "UPDATE inventory SET deleted_at = ?1 WHERE id = ?2 AND deleted_at IS NULL",
+        params![now, id],
+    )?;
+
+    if deleted == 0 {
+        return Err(AppError::NotFound(id));
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+fn delete_item(id: i64, state: State<AppState>) -> Result<(), AppError> {
+    retry_on_busy(|| {
+        let db = state.write_conn()?;
+        soft_delete_item(&db, id, &now_timestamp())?;
+
+        *state.last_deleted.lock()? = Some(id);
+        emit_inventory_changed(&state.app_handle, id, "delete");
+
+        Ok(())
+    })
+}
+
+#[tauri::command]
+fn hard_delete_item(id: i64, state: State<AppState>) -> Result<(), AppError> {
+    let mut db = state.write_conn()?;
+
+    let image_files = with_transaction(&mut db, |tx| delete_item_row(tx, id))?;
+
+    // Los archivos se borran solo cuando la fila ya se eliminó
+    remove_image_files(&image_files);
+    emit_inventory_changed(&state.app_handle, id, "hard_delete");
+
+    Ok(())
+}
+
+// Devuelve las imágenes del artículo que hay que borrar tras confirmar
+fn delete_item_row(db: &Connection, id: i64) -> Result<Vec<String>, AppError> {
+    let image_files = item_image_files(db, id)?;
+
+    let deleted = db.execute("DELETE FROM inventory WHERE id = ?1", params![id])?;
+    if deleted == 0 {
+        return Err(AppError::NotFound(id));
+    }
+
+    Ok(image_files)
+}
+
+#[tauri::command]
+fn restore_item(id: i64, state: State<AppState>) -> Result<InventoryItem, AppError> {
+    let db = state.write_conn()?;
+
+    restore_trashed_item(&db, id)?;
+
+    let item = fetch_item(&db, id)?;
+    emit_inventory_changed(&state.app_handle, id, "restore");
+
+    Ok(item)
+}
+
+// Solo artículos en la papelera; otro artículo activo pudo tomar su nombre
+// mientras tanto
+fn restore_trashed_item(db: &Connection, id: i64) -> Result<(), AppError> {
+    let name: String = db
+        .query_row(
+            "SELECT name FROM inventory WHERE id = ?1 AND deleted_at IS NOT NULL",
+            [id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or(AppError::NotFound(id))?;
+    ensure_unique_name(db, &name, Some(id))?;
+
+    let restored = db.execute(
+        "UPDATE inventory SET deleted_at = NULL WHERE id = ?1 AND deleted_at IS NOT NULL",
+        [id],
+    )?;
+    if restored == 0 {
+        return Err(AppError::NotFound(id));
+    }
+
+    Ok(())
+}
+
+// Como delete_item solo envía a la papelera (la imagen se conserva), deshacer
+// es restaurar el último artículo eliminado. Si mientras tanto se eliminó
+// definitivamente o ya se restauró, no hay nada que deshacer.
+#[tauri::command]
+fn undo_last_delete(state: State<AppState>) -> Result<InventoryItem, AppError> {
+    let db = state.write_conn()?;
+
+    let nothing_to_undo = || AppError::InvalidInput("No hay ninguna eliminación para deshacer".to_string());
+    let id = state.last_deleted.lock()?.take().ok_or_else(nothing_to_undo)?;
+
+    match restore_trashed_item(&db, id) {
+        Ok(()) => {}
+        Err(AppError::NotFound(_)) => return Err(nothing_to_undo()),
+        Err(e) => return Err(e),
+    }
+
+    let item = fetch_item(&db, id)?;
+    emit_inventory_changed(&state.app_handle, id, "restore");
+
+    Ok(item)
+}
+
+#[tauri::command]
+fn get_deleted_items(state: State<AppState>) -> Result<Vec<InventoryItem>, AppError> {
+    let db = state.conn()?;
+    let mut stmt = db.prepare(&format!(
+        "SELECT {} FROM inventory WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC, id DESC",
+        ITEM_COLUMNS
+    ))?;
+
+    let items = stmt
+        .query_map([], item_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(items)
+}
+
+// Igual que delete_item, pero para varios: van a la papelera y las imágenes se
+// conservan. Los ids que no existen o ya estaban en la papelera no cuentan.
+#[tauri::command]
+fn bulk_delete(ids: Vec<i64>, state: State<AppState>) -> Result<usize, AppError> {
+    let deleted = retry_on_busy(|| {
+        let mut db = state.write_conn()?;
+        let now = now_timestamp();
+
+        with_transaction(&mut db, |tx| soft_delete_items(tx, &ids, &now))
+    })?;
+
+    // Deshacer solo restaura un artículo; después de un lote no debe traer de
+    // vuelta una eliminación anterior
+    if !deleted.is_empty() {
+        *state.last_deleted.lock()? = None;
+    }
+
+    for id in &deleted {
+        emit_inventory_changed(&state.app_handle, *id, "delete");
+    }
+
+    Ok(deleted.len())
+}
+
+// Devuelve los ids que sí se enviaron a la papelera
+fn soft_delete_items(db: &Connection, ids: &[i64], now: &str) -> Result<Vec<i64>, AppError> {
+    let mut deleted = Vec::new();
+    for id in ids {
+        match soft_delete_item(db, *id, now) {
+            Ok(()) => deleted.push(*id),
+            Err(AppError::NotFound(_)) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(deleted)
+}
+
+// Copia de un artículo para crear otro parecido: " (copia)" en el nombre, sin
+// existencias ni código de barras (es único). La imagen y la miniatura se
+// copian a archivos propios de la copia, igual que las guardadas en la base de
+// datos.
+#[tauri::command]
+fn duplicate_item(id: i64, state: State<AppState>) -> Result<InventoryItem, AppError> {
+    let mut db = state.write_conn()?;
+    let now = now_timestamp();
+
+    let mut created_files = Vec::new();
+    let new_id = match with_transaction(&mut db, |tx| copy_item(tx, id, &now, &mut created_files)) {
+        Ok(new_id) => new_id,
+        Err(e) => {
+            remove_image_files(&created_files);
+            return Err(e);
+        }
+    };
+
+    emit_inventory_changed(&state.app_handle, new_id, "add");
+
+    fetch_item(&db, new_id)
+}
+
+// Parte de duplicate_item que solo toca la base de datos; los archivos de
+// imagen copiados quedan en created_files. Devuelve el id de la copia.
+fn copy_item(db: &Connection, id: i64, now: &str, created_files: &mut Vec<String>) -> Result<i64, AppError> {
+    let source = fetch_item(db, id)?;
+
+    // Si la copia ya existe se numera: "Tornillo (copia 2)"
+    let mut name = format!("{} (copia)", source.name);
+    let mut counter = 2;
+    loop {
+        match ensure_unique_name(db, &name, None) {
+            Ok(()) => break,
+            Err(AppError::DuplicateName(_)) => {
+                name = format!("{} (copia {})", source.name, counter);
+                counter += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    db.execute(
+        "INSERT INTO inventory (name, image_path, cantidad_necesaria, cantidad_disponible, created_at, updated_at, category,
+             thumbnail_path, name_normalized, precio_unitario, costo_unitario, supplier_id, descripcion, unidad, punto_reorden,
+             capacidad_maxima, image_source_mime)
+         SELECT ?1, image_path, cantidad_necesaria, 0, ?2, ?2, category, thumbnail_path, ?3, precio_unitario,
+             costo_unitario, supplier_id, descripcion, unidad, punto_reorden, capacidad_maxima, image_source_mime
+         FROM inventory WHERE id = ?4",
+        params![name, now, normalize_name(&name), id],
+    )?;
+    let new_id = db.last_insert_rowid();
+
+    db.execute(
+        "INSERT INTO item_images (item_id, mime, data) SELECT ?1, mime, data FROM item_images WHERE item_id = ?2",
+        params![new_id, id],
+    )?;
+    db.execute(
+        "INSERT INTO item_tags (item_id, tag_id) SELECT ?1, tag_id FROM item_tags WHERE item_id = ?2",
+        params![new_id, id],
+    )?;
+
+    let (image_path, thumbnail_path) = copy_item_image_files(&source, new_id, created_files)?;
+    db.execute(
+        "UPDATE inventory SET image_path = ?1, thumbnail_path = ?2 WHERE id = ?3",
+        params![image_path, thumbnail_path, new_id],
+    )?;
+
+    Ok(new_id)
+}
+
+// Une dos artículos duplicados: se suman las cantidades disponibles y se
+// conserva la mayor cantidad necesaria salvo que se indique otra. El historial
+// y las etiquetas pasan al artículo que queda.
+#[tauri::command]
+fn merge_items(
+    keep_id: i64,
+    remove_id: i64,
+    cantidad_necesaria: Option<f64>,
+    state: State<AppState>,
+) -> Result<InventoryItem, AppError> {
+    let mut db = state.write_conn()?;
+    let now = now_timestamp();
+
+    let (item, image_files) =
+        with_transaction(&mut db, |tx| merge_item_rows(tx, keep_id, remove_id, cantidad_necesaria, &now))?;
+
+    remove_image_files(&image_files);
+    emit_inventory_changed(&state.app_handle, keep_id, "update");
+    emit_inventory_changed(&state.app_handle, remove_id, "hard_delete");
+
+    Ok(item)
+}
+
+// Parte de merge_items que solo toca la base de datos. Devuelve el artículo que
+// queda y las imágenes del eliminado que hay que borrar tras confirmar.
+fn merge_item_rows(
+    db: &Connection,
+    keep_id: i64,
+    remove_id: i64,
+    cantidad_necesaria: Option<f64>,
+    now: &str,
+) -> Result<(InventoryItem, Vec<String>), AppError> {
+    if keep_id == remove_id {
+        return Err(AppError::InvalidInput("No se puede fusionar un artículo consigo mismo".to_string()));
+    }
+
+    let keep = fetch_item(db, keep_id)?;
+    let remove = fetch_item(db, remove_id)?;
+
+    let cantidad_disponible = round_quantity(keep.cantidad_disponible + remove.cantidad_disponible);
+    validate_capacity(cantidad_disponible, keep.capacidad_maxima)?;
+    let cantidad_necesaria = round_quantity(
+        cantidad_necesaria.unwrap_or(keep.cantidad_necesaria.max(remove.cantidad_necesaria)),
+    );
+    validate_quantities(cantidad_necesaria, cantidad_disponible)?;
+
+    db.execute(
+        "UPDATE inventory SET cantidad_disponible = ?1, cantidad_necesaria = ?2, updated_at = ?3, version = version + 1
+         WHERE id = ?4",
+        params![cantidad_disponible, cantidad_necesaria, now, keep_id],
+    )?;
+
+    db.execute(
+        "UPDATE stock_movements SET item_id = ?1 WHERE item_id = ?2",
+        params![keep_id, remove_id],
+    )?;
+    db.execute(
+        "INSERT OR IGNORE INTO item_tags (item_id, tag_id) SELECT ?1, tag_id FROM item_tags WHERE item_id = ?2",
+        params![keep_id, remove_id],
+    )?;
+
+    let reason = format!("Fusión con \"{}\" (id {})", remove.name, remove_id);
+    record_movement(db, keep_id, keep.cantidad_disponible, cantidad_disponible, Some(&reason), now)?;
+
+    // El trigger de item_tags limpia las asociaciones del artículo eliminado
+    let image_files = item_image_files(db, remove_id)?;
+    db.execute("DELETE FROM inventory WHERE id = ?1", [remove_id])?;
+
+    let item = fetch_item(db, keep_id)?;
+    Ok((item, image_files))
+}
+
+#[tauri::command]
+fn get_low_stock_items(state: State<AppState>) -> Result<Vec<InventoryItem>, AppError> {
+    let db = state.conn()?;
+    Ok(query_low_stock_items(&db, -1)?)
+}
+
+// Los que más faltan primero; un límite negativo los devuelve todos
+fn query_low_stock_items(db: &Connection, limit: i64) -> Result<Vec<InventoryItem>> {
+    let mut stmt = db
+        .prepare(&format!(
+            "SELECT {} FROM inventory
+             WHERE deleted_at IS NULL AND {}
+             ORDER BY (cantidad_necesaria - cantidad_disponible) DESC, id
+             LIMIT ?1",
+            ITEM_COLUMNS,
+            low_stock_condition()
+        ))?;
+
+    let items = stmt
+        .query_map([limit], item_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(items)
+}
+
+// Artículos agotados; a diferencia del stock bajo incluye los que no tienen
+// cantidad necesaria
+#[tauri::command]
+fn get_out_of_stock_items(state: State<AppState>) -> Result<Vec<InventoryItem>, AppError> {
+    let db = state.conn()?;
+    let mut stmt = db.prepare(&format!(
+        "SELECT {} FROM inventory
+         WHERE deleted_at IS NULL AND cantidad_disponible < ?1
+         ORDER BY name COLLATE NOCASE, id",
+        ITEM_COLUMNS
+    ))?;
+
+    let items = stmt
+        .query_map([QUANTITY_EPSILON], item_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(items)
+}
+
+// Artículos por debajo de una fracción de lo necesario: 1.0 equivale a
+// "por debajo de lo necesario" y 0.5 a "críticamente bajo". Los artículos
+// sin cantidad necesaria quedan fuera.
+#[tauri::command]
+fn get_items_by_stock_ratio(max_ratio: f64, state: State<AppState>) -> Result<Vec<InventoryItem>, AppError> {
+    if !max_ratio.is_finite() || max_ratio < 0.0 {
+        return Err(AppError::InvalidInput("La proporción debe ser un número positivo".to_string()));
+    }
+
+    let db = state.conn()?;
+    let mut stmt = db.prepare(&format!(
+        "SELECT {} FROM inventory
+         WHERE deleted_at IS NULL AND cantidad_necesaria > 0 AND cantidad_disponible < cantidad_necesaria * ?1
+         ORDER BY CAST(cantidad_disponible AS REAL) / cantidad_necesaria, name COLLATE NOCASE, id",
+        ITEM_COLUMNS
+    ))?;
+
+    let items = stmt
+        .query_map([max_ratio], item_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(items)
+}
+
+// Porcentaje de cobertura de cada artículo (disponible / necesario, máximo
+// 100), los peor surtidos primero y al final los que no tienen objetivo
+#[tauri::command]
+fn get_variance_report(state: State<AppState>) -> Result<Vec<VarianceLine>, AppError> {
+    let db = state.conn()?;
+    let mut stmt = db.prepare(
+        "SELECT id, name, cantidad_necesaria, cantidad_disponible, fill_percent FROM (
+             SELECT id, name, cantidad_necesaria, cantidad_disponible,
+                 CASE WHEN cantidad_necesaria > 0
+                     THEN MIN(100.0, cantidad_disponible * 100.0 / cantidad_necesaria)
+                 END AS fill_percent
+             FROM inventory WHERE deleted_at IS NULL
+         )
+         ORDER BY fill_percent IS NULL, fill_percent, name COLLATE NOCASE, id",
+    )?;
+
+    let lines = stmt
+        .query_map([], |row| {
+            Ok(VarianceLine {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                needed: row.get(2)?,
+                available: row.get(3)?,
+                fill_percent: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(lines)
+}
+
+// Máximo de líneas del listado de faltantes
+const TOP_SHORTFALL_MAX: i64 = 100;
+
+// Los artículos que más faltan para llegar a lo necesario; los que ya lo
+// alcanzan no aparecen
+#[tauri::command]
+fn get_top_shortfall(limit: i64, state: State<AppState>) -> Result<Vec<ShortfallLine>, AppError> {
+    let db = state.conn()?;
+    let mut stmt = db.prepare(
+        "SELECT id, name, cantidad_necesaria, cantidad_disponible,
+                ROUND(cantidad_necesaria - cantidad_disponible, ?2) AS shortfall
+         FROM inventory
+         WHERE deleted_at IS NULL AND cantidad_disponible < cantidad_necesaria - ?3
+         ORDER BY shortfall DESC, name COLLATE NOCASE, id
+         LIMIT ?1",
+    )?;
+
+    let lines = stmt
+        .query_map(params![limit.clamp(0, TOP_SHORTFALL_MAX), QUANTITY_DECIMALS, QUANTITY_EPSILON], |row| {
+            Ok(ShortfallLine {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                needed: row.get(2)?,
+                available: row.get(3)?,
+                shortfall: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(lines)
+}
+
+// Las líneas quedan agrupadas por proveedor (los artículos sin proveedor al
+// final) y dentro de cada grupo de mayor a menor faltante
+#[tauri::command]
+fn get_reorder_report(state: State<AppState>) -> Result<Vec<ReorderLine>, AppError> {
+    let percent = low_stock_percent(&state.app_handle);
+    let db = state.conn()?;
+    // Se pide al llegar al punto de reorden, pero siempre hasta completar lo necesario
+    let mut stmt = db.prepare(
+        "SELECT i.id, i.name, i.cantidad_disponible, i.cantidad_necesaria,
+                ROUND(i.cantidad_necesaria - i.cantidad_disponible, ?1) AS shortfall, s.id, s.name
+         FROM inventory i
+         LEFT JOIN suppliers s ON s.id = i.supplier_id
+         WHERE i.deleted_at IS NULL
+           AND i.cantidad_disponible < COALESCE(i.punto_reorden, i.cantidad_necesaria * ?3 / 100.0) - ?2
+           AND i.cantidad_disponible < i.cantidad_necesaria - ?2
+         ORDER BY s.id IS NULL, s.name COLLATE NOCASE, s.id, shortfall DESC, i.name COLLATE NOCASE, i.id",
+    )?;
+
+    let lines = stmt
+        .query_map(params![QUANTITY_DECIMALS, QUANTITY_EPSILON, percent], |row| {
+            Ok(ReorderLine {
+                item_id: row.get(0)?,
+                name: row.get(1)?,
+                cantidad_disponible: row.get(2)?,
+                cantidad_necesaria: row.get(3)?,
+                shortfall: row.get(4)?,
+                supplier_id: row.get(5)?,
+                supplier_name: row.get(6)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(lines)
+}
+
+#[tauri::command]
+fn get_statistics(state: State<AppState>) -> Result<Stats, AppError> {
+    let db = state.conn()?;
+    Ok(query_statistics(&db)?)
+}
+
+fn query_statistics(db: &Connection) -> Result<Stats> {
+    let stats = db.query_row(
+        &format!(
+            "SELECT
+                COUNT(*),
+                ROUND(TOTAL(cantidad_disponible), {decimals}),
+                ROUND(TOTAL(cantidad_necesaria), {decimals}),
+                COALESCE(SUM(CASE WHEN {low_stock} THEN 1 ELSE 0 END), 0),
+                COALESCE(SUM(CASE WHEN cantidad_disponible < {epsilon} THEN 1 ELSE 0 END), 0)
+             FROM inventory
+             WHERE deleted_at IS NULL",
+            decimals = QUANTITY_DECIMALS,
+            epsilon = QUANTITY_EPSILON,
+            low_stock = low_stock_condition()
+        ),
+        [],
+        |row| {
+            Ok(Stats {
+                total_items: row.get(0)?,
+                total_available: row.get(1)?,
+                total_needed: row.get(2)?,
+                low_stock_count: row.get(3)?,
+                out_of_stock_count: row.get(4)?,
+            })
+        },
+    )?;
+
+    Ok(stats)
+}
+
+const DASHBOARD_LOW_STOCK_LIMIT: i64 = 10;
+const DASHBOARD_MOVEMENTS_LIMIT: i64 = 20;
+
+// Todo lo que muestra el panel en una sola llamada y con una sola conexión
+#[tauri::command]
+fn get_dashboard_data(state: State<AppState>) -> Result<DashboardData, AppError> {
+    let db = state.conn()?;
+
+    Ok(DashboardData {
+        stats: query_statistics(&db)?,
+        low_stock_items: query_low_stock_items(&db, DASHBOARD_LOW_STOCK_LIMIT)?,
+        recent_movements: query_recent_movements(&db, DASHBOARD_MOVEMENTS_LIMIT)?,
+    })
+}
+
+#[tauri::command]
+fn get_inventory_value(state: State<AppState>) -> Result<ValuationReport, AppError> {
+    let db = state.conn()?;
+
+    // Los artículos sin precio o costo suman cero
+    let report = db.query_row(
+        "SELECT
+            COALESCE(SUM(COALESCE(costo_unitario, 0) * cantidad_disponible), 0),
+            COALESCE(SUM(COALESCE(precio_unitario, 0) * cantidad_disponible), 0)
+         FROM inventory
+         WHERE deleted_at IS NULL",
+        [],
+        |row| {
+            Ok(ValuationReport {
+                total_cost_value: row.get(0)?,
+                total_retail_value: row.get(1)?,
+            })
+        },
+    )?;
+
+    Ok(report)
+}
+
+#[tauri::command]
+fn get_categories(state: State<AppState>) -> Result<Vec<String>, AppError> {
+    let db = state.conn()?;
+    let mut stmt = db.prepare("SELECT DISTINCT category FROM inventory WHERE category IS NOT NULL AND deleted_at IS NULL ORDER BY category")?;
+
+    let categories = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+
+    Ok(categories)
+}
+
+const UNCATEGORIZED_LABEL: &str = "Sin categoría";
+
+// Una sola consulta ordenada por categoría; los grupos se arman al recorrerla.
+// El grupo sin categoría va al final y los archivados no aparecen.
+#[tauri::command]
+fn get_items_grouped(state: State<AppState>) -> Result<Vec<CategoryGroup>, AppError> {
+    let db = state.conn()?;
+    // El segundo criterio por categoría mantiene juntas las que solo difieren
+    // en mayúsculas
+    let mut stmt = db.prepare(&format!(
+        "SELECT {} FROM inventory WHERE deleted_at IS NULL AND archived = 0
+         ORDER BY category IS NULL, category COLLATE NOCASE, category, name COLLATE NOCASE, id",
+        ITEM_COLUMNS
+    ))?;
+
+    let items = stmt
+        .query_map([], item_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut groups: Vec<CategoryGroup> = Vec::new();
+    for item in items {
+        let category = item.category.clone().unwrap_or_else(|| UNCATEGORIZED_LABEL.to_string());
+        match groups.last_mut() {
+            Some(group) if group.category == category => group.items.push(item),
+            _ => groups.push(CategoryGroup {
+                category,
+                count: 0,
+                items: vec![item],
+            }),
+        }
+    }
+    for group in &mut groups {
+        group.count = group.items.len();
+    }
+
+    Ok(groups)
+}
+
+fn supplier_from_row(row: &rusqlite::Row) -> Result<Supplier> {
+    Ok(Supplier {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        contact: row.get(2)?,
+        phone: row.get(3)?,
+    })
+}
+
+#[tauri::command]
+fn add_supplier(
+    name: String,
+    contact: Option<String>,
+    phone: Option<String>,
+    state: State<AppState>,
+) -> Result<Supplier, AppError> {
+    let db = state.write_conn()?;
+
+    db.execute(
+        "INSERT INTO suppliers (name, contact, phone) VALUES (?1, ?2, ?3)",
+        params![name, contact, phone],
+    )?;
+
+    let supplier = db.query_row(
+        "SELECT id, name, contact, phone FROM suppliers WHERE id = ?1",
+        [db.last_insert_rowid()],
+        supplier_from_row,
+    )?;
+
+    Ok(supplier)
+}
+
+#[tauri::command]
+fn get_suppliers(state: State<AppState>) -> Result<Vec<Supplier>, AppError> {
+    let db = state.conn()?;
+    let mut stmt = db.prepare("SELECT id, name, contact, phone FROM suppliers ORDER BY name COLLATE NOCASE")?;
+
+    let suppliers = stmt
+        .query_map([], supplier_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(suppliers)
+}
+
+#[tauri::command]
+fn update_supplier(
+    id: i64,
+    name: String,
+    contact: Option<String>,
+    phone: Option<String>,
+    state: State<AppState>,
+) -> Result<Supplier, AppError> {
+    let db = state.write_conn()?;
+
+    let updated = db.execute(
+        "UPDATE suppliers SET name = ?1, contact = ?2, phone = ?3 WHERE id = ?4",
+        params![name, contact, phone, id],
+    )?;
+
+    if updated == 0 {
+        return Err(AppError::SupplierNotFound(id));
+    }
+
+    let supplier = db.query_row(
+        "SELECT id, name, contact, phone FROM suppliers WHERE id = ?1",
+        [id],
+        supplier_from_row,
+    )?;
+
+    Ok(supplier)
+}
+
+// Los artículos del proveedor quedan sin proveedor en lugar de impedir el borrado
+#[tauri::command]
+fn delete_supplier(id: i64, state: State<AppState>) -> Result<(), AppError> {
+    let mut db = state.write_conn()?;
+    let tx = db.transaction()?;
+
+    tx.execute("UPDATE inventory SET supplier_id = NULL WHERE supplier_id = ?1", [id])?;
+    let deleted = tx.execute("DELETE FROM suppliers WHERE id = ?1", [id])?;
+
+    if deleted == 0 {
+        return Err(AppError::SupplierNotFound(id));
+    }
+
+    tx.commit()?;
+
+    Ok(())
+}
+
+#[tauri::command]
+fn get_items_by_supplier(supplier_id: i64, state: State<AppState>) -> Result<Vec<InventoryItem>, AppError> {
+    let db = state.conn()?;
+    let mut stmt = db.prepare(&format!(
+        "SELECT {} FROM inventory WHERE deleted_at IS NULL AND supplier_id = ?1 ORDER BY name COLLATE NOCASE, id",
+        ITEM_COLUMNS
+    ))?;
+
+    let items = stmt
+        .query_map([supplier_id], item_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(items)
+}
+
+// La etiqueta se crea si todavía no existe (sin distinguir mayúsculas)
+#[tauri::command]
+fn add_tag_to_item(item_id: i64, tag: String, state: State<AppState>) -> Result<Tag, AppError> {
+    let tag = tag.trim();
+    if tag.is_empty() {
+        return Err(AppError::InvalidInput("El nombre de la etiqueta no puede estar vacío".to_string()));
+    }
+
+    let mut db = state.write_conn()?;
+    let tx = db.transaction()?;
+
+    let exists: bool = tx.query_row(
+        "SELECT EXISTS(SELECT 1 FROM inventory WHERE id = ?1)",
+        [item_id],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        return Err(AppError::NotFound(item_id));
+    }
+
+    tx.execute("INSERT OR IGNORE INTO tags (name) VALUES (?1)", [tag])?;
+    let tag = tx.query_row("SELECT id, name FROM tags WHERE name = ?1", [tag], |row| {
+        Ok(Tag {
+            id: row.get(0)?,
+            name: row.get(1)?,
+        })
+    })?;
+
+    tx.execute(
+        "INSERT OR IGNORE INTO item_tags (item_id, tag_id) VALUES (?1, ?2)",
+        params![item_id, tag.id],
+    )?;
+
+    tx.commit()?;
+
+    Ok(tag)
+}
+
+#[tauri::command]
+fn remove_tag_from_item(item_id: i64, tag_id: i64, state: State<AppState>) -> Result<(), AppError> {
+    let db = state.write_conn()?;
+
+    db.execute(
+        "DELETE FROM item_tags WHERE item_id = ?1 AND tag_id = ?2",
+        params![item_id, tag_id],
+    )?;
+
+    Ok(())
+}
+
+#[tauri::command]
+fn get_tags(state: State<AppState>) -> Result<Vec<Tag>, AppError> {
+    let db = state.conn()?;
+    let mut stmt = db.prepare("SELECT id, name FROM tags ORDER BY name COLLATE NOCASE")?;
+
+    let tags = stmt
+        .query_map([], |row| {
+            Ok(Tag {
+                id: row.get(0)?,
+                name: row.get(1)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(tags)
+}
+
+#[tauri::command]
+fn get_items_by_tag(tag_id: i64, state: State<AppState>) -> Result<Vec<InventoryItem>, AppError> {
+    let db = state.conn()?;
+    let mut stmt = db.prepare(&format!(
+        "SELECT {} FROM inventory
+         WHERE deleted_at IS NULL AND id IN (SELECT item_id FROM item_tags WHERE tag_id = ?1)
+         ORDER BY name COLLATE NOCASE, id",
+        ITEM_COLUMNS
+    ))?;
+
+    let items = stmt
+        .query_map([tag_id], item_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(items)
+}
+
+// Quita la etiqueta de todos los artículos; los artículos no se tocan
+#[tauri::command]
+fn delete_tag(tag_id: i64, state: State<AppState>) -> Result<(), AppError> {
+    let mut db = state.write_conn()?;
+    let tx = db.transaction()?;
+
+    tx.execute("DELETE FROM item_tags WHERE tag_id = ?1", [tag_id])?;
+    let deleted = tx.execute("DELETE FROM tags WHERE id = ?1", [tag_id])?;
+
+    if deleted == 0 {
+        return Err(AppError::TagNotFound(tag_id));
+    }
+
+    tx.commit()?;
+
+    Ok(())
+}
+
+#[tauri::command]
+fn export_csv(path: String, state: State<AppState>) -> Result<(), AppError> {
+    let filter = ItemFilter {
+        include_archived: true,
+        ..Default::default()
+    };
+    export_csv_filtered(path, filter, state)
+}
+
+// Exporta solo lo que muestra el listado con los filtros actuales
+#[tauri::command]
+fn export_csv_filtered(path: String, filter: ItemFilter, state: State<AppState>) -> Result<(), AppError> {
+    let db = state.conn()?;
+    let items = query_items(&db, &filter)?;
+    drop(db);
+
+    let mut writer = csv::Writer::from_path(&path)?;
+    writer.write_record(["id", "name", "cantidad_necesaria", "cantidad_disponible", "created_at", "has_image"])?;
+
+    // La ruta de la imagen no sirve en una hoja de cálculo, solo se indica si existe
+    for item in items {
+        writer.write_record([
+            item.id.map(|id| id.to_string()).unwrap_or_default(),
+            item.name,
+            item.cantidad_necesaria.to_string(),
+            item.cantidad_disponible.to_string(),
+            item.created_at.unwrap_or_default(),
+            item.image_path.is_some().to_string(),
+        ])?;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+// Cantidad de una celda CSV; una celda vacía cuenta como 0
+fn parse_csv_quantity(value: Option<&str>, column: &str) -> Result<f64, String> {
+    let value = value.map(str::trim).unwrap_or("");
+    if value.is_empty() {
+        return Ok(0.0);
+    }
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|quantity| quantity.is_finite())
+        .map(round_quantity)
+        .ok_or_else(|| format!("{} no es un número válido: \"{}\"", column, value))
+}
+
+#[tauri::command]
+fn import_csv(path: String, dry_run: bool, state: State<AppState>) -> Result<ImportReport, AppError> {
+    let mut reader = csv::Reader::from_path(&path)?;
+
+    let mut db = state.write_conn()?;
+    let tx = db.transaction()?;
+
+    let report = import_csv_rows(&tx, &mut reader, dry_run, &now_timestamp())?;
+
+    // En modo de prueba solo se valida y se descartan los cambios
+    if dry_run {
+        tx.rollback()?;
+    } else {
+        tx.commit()?;
+    }
+
+    Ok(report)
+}
+
+// Parte de import_csv que solo toca la base de datos; quien llama confirma o
+// descarta los cambios
+fn import_csv_rows<R: std::io::Read>(
+    db: &Connection,
+    reader: &mut csv::Reader<R>,
+    dry_run: bool,
+    now: &str,
+) -> Result<ImportReport, AppError> {
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| headers.iter().position(|h| h.trim().eq_ignore_ascii_case(name));
+
+    let name_col = column("name").ok_or_else(|| {
+        AppError::InvalidInput("El archivo CSV no tiene la columna \"name\"".to_string())
+    })?;
+    let necesaria_col = column("cantidad_necesaria");
+    let disponible_col = column("cantidad_disponible");
+    let category_col = column("category");
+    let created_col = column("created_at");
+
+    let mut report = ImportReport {
+        inserted: 0,
+        skipped: 0,
+        errors: Vec::new(),
+        dry_run,
+    };
+
+    for (index, record) in reader.records().enumerate() {
+        // Fila 1 es el encabezado
+        let row = index + 2;
+
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                report.skipped += 1;
+                report.errors.push(ImportRowError { row, message: e.to_string() });
+                continue;
+            }
+        };
+
+        let cantidades = parse_csv_quantity(necesaria_col.and_then(|c| record.get(c)), "cantidad_necesaria")
+            .and_then(|necesaria| {
+                parse_csv_quantity(disponible_col.and_then(|c| record.get(c)), "cantidad_disponible")
+                    .map(|disponible| (necesaria, disponible))
+            });
+
+        let (cantidad_necesaria, cantidad_disponible) = match cantidades {
+            Ok(cantidades) => cantidades,
+            Err(message) => {
+                report.skipped += 1;
+                report.errors.push(ImportRowError { row, message });
+                continue;
+            }
+        };
+
+        // Las mismas validaciones que al crear un artículo a mano; la fecha
+        // original se conserva si el archivo la trae
+        let item = NewItem {
+            name: record.get(name_col).unwrap_or("").to_string(),
+            image_base64: None,
+            cantidad_necesaria,
+            cantidad_disponible,
+            category: category_col.and_then(|c| record.get(c)).map(str::to_string),
+            precio_unitario: None,
+            costo_unitario: None,
+            supplier_id: None,
+            descripcion: None,
+            codigo_barras: None,
+            unidad: None,
+            punto_reorden: None,
+            capacidad_maxima: None,
+            created_at: created_col
+                .and_then(|c| record.get(c))
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(str::to_string),
+            reason: Some("Importación CSV".to_string()),
+            allow_duplicate: false,
+        };
+
+        match create_item(db, &item, None, now) {
+            Ok(_) => {}
+            // Un fallo de la base de datos no es culpa de la fila: se aborta
+            // la importación y la transacción se revierte
+            Err(e @ (AppError::Database(_) | AppError::DatabaseBusy | AppError::LockPoisoned | AppError::Io(_))) => {
+                return Err(e)
+            }
+            Err(e) => {
+                report.skipped += 1;
+                report.errors.push(ImportRowError { row, message: e.to_string() });
+                continue;
+            }
+        }
+
+        report.inserted += 1;
+    }
+
+    Ok(report)
+}
+
+// Reporte imprimible; las filas con poco stock se marcan en rojo
+#[tauri::command]
+fn export_pdf(path: String, state: State<AppState>) -> Result<(), AppError> {
+    let percent = low_stock_percent(&state.app_handle);
+    let db = state.conn()?;
+    write_pdf_report(&db, percent, Path::new(&path))
+}
+
+// Parte de export_pdf que no depende de la aplicación; percent es el umbral de
+// stock bajo ya leído de las preferencias
+fn write_pdf_report(db: &Connection, percent: f64, path: &Path) -> Result<(), AppError> {
+    use printpdf::{BuiltinFont, Color, Mm, PdfDocument, PdfLayerReference, Rgb};
+
+    // Medidas en milímetros (A4)
+    const PAGE_WIDTH: f32 = 210.0;
+    const PAGE_HEIGHT: f32 = 297.0;
+    const MARGIN: f32 = 15.0;
+    const ROW_HEIGHT: f32 = 6.0;
+    const MAX_NAME_CHARS: usize = 55;
+    const COLUMNS: [f32; 4] = [MARGIN, 120.0, 145.0, 170.0];
+
+    let mut stmt = db.prepare(&format!(
+        "SELECT {} FROM inventory WHERE deleted_at IS NULL ORDER BY name COLLATE NOCASE, id",
+        ITEM_COLUMNS
+    ))?;
+
+    let items = stmt
+        .query_map([], item_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    drop(stmt);
+
+    let pdf_error = |e: printpdf::Error| AppError::Io(e.to_string());
+    // Solo se muestra en el reporte, así que va en hora local
+    let generated_at = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+
+    let (doc, page, layer) = PdfDocument::new("Reporte de inventario", Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Capa 1");
+    let font = doc.add_builtin_font(BuiltinFont::Helvetica).map_err(pdf_error)?;
+    let bold = doc.add_builtin_font(BuiltinFont::HelveticaBold).map_err(pdf_error)?;
+    let black = Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None));
+    let red = Color::Rgb(Rgb::new(0.8, 0.0, 0.0, None));
+
+    let write_header = |layer: &PdfLayerReference, y: f32| {
+        layer.set_fill_color(black.clone());
+        for (title, x) in ["Artículo", "Necesaria", "Disponible", "Estado"].iter().zip(COLUMNS) {
+            layer.use_text(*title, 10.0, Mm(x), Mm(y), &bold);
+        }
+    };
+
+    let mut layer = doc.get_page(page).get_layer(layer);
+    let mut y = PAGE_HEIGHT - MARGIN;
+
+    layer.use_text("Reporte de inventario", 16.0, Mm(MARGIN), Mm(y), &bold);
+    y -= 8.0;
+    layer.use_text(
+        format!("Generado: {}    Total de artículos: {}", generated_at, items.len()),
+        10.0,
+        Mm(MARGIN),
+        Mm(y),
+        &font,
+    );
+    y -= 10.0;
+    write_header(&layer, y);
+    y -= ROW_HEIGHT;
+
+    for item in &items {
+        // Nueva página con su propio encabezado de columnas
+        if y < MARGIN {
+            let (page, page_layer) = doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Capa 1");
+            layer = doc.get_page(page).get_layer(page_layer);
+            y = PAGE_HEIGHT - MARGIN;
+            write_header(&layer, y);
+            y -= ROW_HEIGHT;
+        }
+
+        let (status, flagged) = if item.cantidad_necesaria > 0.0 && item.cantidad_disponible < QUANTITY_EPSILON {
+            ("Sin stock", true)
+        } else if is_low_stock(item.cantidad_disponible, item.cantidad_necesaria, item.punto_reorden, percent) {
+            ("Bajo", true)
+        } else {
+            ("OK", false)
+        };
+
+        let name = if item.name.chars().count() > MAX_NAME_CHARS {
+            format!("{}...", item.name.chars().take(MAX_NAME_CHARS - 3).collect::<String>())
+        } else {
+            item.name.clone()
+        };
+
+        layer.set_fill_color(if flagged { red.clone() } else { black.clone() });
+        let cells = [
+            name,
+            item.cantidad_necesaria.to_string(),
+            item.cantidad_disponible.to_string(),
+            status.to_string(),
+        ];
+        for (cell, x) in cells.into_iter().zip(COLUMNS) {
+            layer.use_text(cell, 10.0, Mm(x), Mm(y), &font);
+        }
+        y -= ROW_HEIGHT;
+    }
+
+    let mut writer = std::io::BufWriter::new(fs::File::create(path)?);
+    doc.save(&mut writer).map_err(pdf_error)?;
+
+    Ok(())
+}
+
+// Hoja de cálculo con formato: encabezado fijo en negrita, filas con poco
+// stock resaltadas en rojo y una fila final con los totales
+#[tauri::command]
+fn export_xlsx(path: String, state: State<AppState>) -> Result<(), AppError> {
+    use rust_xlsxwriter::{Color, Format, FormatAlign, Workbook};
+
+    let db = state.conn()?;
+    let mut stmt = db.prepare(&format!(
+        "SELECT {} FROM inventory WHERE deleted_at IS NULL ORDER BY name COLLATE NOCASE, id",
+        ITEM_COLUMNS
+    ))?;
+
+    let items = stmt
+        .query_map([], item_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    drop(stmt);
+    drop(db);
+    let percent = low_stock_percent(&state.app_handle);
+
+    let low_fill = Color::RGB(0xFFC7CE);
+    let header = Format::new().set_bold();
+    let text = Format::new();
+    let number = Format::new().set_align(FormatAlign::Right);
+    let money = Format::new().set_align(FormatAlign::Right).set_num_format("#,##0.00");
+    let low_text = text.clone().set_background_color(low_fill);
+    let low_number = number.clone().set_background_color(low_fill);
+    let low_money = money.clone().set_background_color(low_fill);
+    let total_label = Format::new().set_bold();
+    let total_number = number.clone().set_bold();
+
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+    worksheet.set_name("Inventario")?;
+
+    let headers = ["Artículo", "Categoría", "Unidad", "Necesaria", "Disponible", "Precio unitario", "Costo unitario"];
+    for (col, title) in headers.iter().enumerate() {
+        worksheet.write_string_with_format(0, col as u16, *title, &header)?;
+    }
+    worksheet.set_freeze_panes(1, 0)?;
+    worksheet.set_column_width(0, 40)?;
+    worksheet.set_column_width(1, 20)?;
+    for col in 2..headers.len() as u16 {
+        worksheet.set_column_width(col, 15)?;
+    }
+
+    let mut total_needed = 0.0;
+    let mut total_available = 0.0;
+
+    for (index, item) in items.iter().enumerate() {
+        let row = index as u32 + 1;
+        let low = is_low_stock(item.cantidad_disponible, item.cantidad_necesaria, item.punto_reorden, percent);
+        let (text, number, money) = if low {
+            (&low_text, &low_number, &low_money)
+        } else {
+            (&text, &number, &money)
+        };
+
+        worksheet.write_string_with_format(row, 0, &item.name, text)?;
+        worksheet.write_string_with_format(row, 1, item.category.as_deref().unwrap_or(""), text)?;
+        worksheet.write_string_with_format(row, 2, item.unidad.as_deref().unwrap_or(""), text)?;
+        worksheet.write_number_with_format(row, 3, item.cantidad_necesaria, number)?;
+        worksheet.write_number_with_format(row, 4, item.cantidad_disponible, number)?;
+        match item.precio_unitario {
+            Some(precio) => worksheet.write_number_with_format(row, 5, precio, money)?,
+            None => worksheet.write_blank(row, 5, money)?,
+        };
+        match item.costo_unitario {
+            Some(costo) => worksheet.write_number_with_format(row, 6, costo, money)?,
+            None => worksheet.write_blank(row, 6, money)?,
+        };
+
+        total_needed += item.cantidad_necesaria;
+        total_available += item.cantidad_disponible;
+    }
+
+    let total_row = items.len() as u32 + 1;
+    worksheet.write_string_with_format(total_row, 0, "Total", &total_label)?;
+    worksheet.write_number_with_format(total_row, 3, round_quantity(total_needed), &total_number)?;
+    worksheet.write_number_with_format(total_row, 4, round_quantity(total_available), &total_number)?;
+
+    workbook.save(&path)?;
+
+    Ok(())
+}
+
+#[tauri::command]
+fn export_json(path: String, state: State<AppState>) -> Result<(), AppError> {
+    let db = state.conn()?;
+    let mut stmt = db.prepare(&format!(
+        "SELECT {} FROM inventory WHERE deleted_at IS NULL ORDER BY created_at DESC, id DESC",
+        ITEM_COLUMNS
+    ))?;
+
+    let items = stmt
+        .query_map([], item_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    drop(stmt);
+
+    let mut backup_items = Vec::with_capacity(items.len());
+    for item in items {
+        let image_base64 = match (item.id, item.image_path.as_deref()) {
+            (Some(id), Some(path)) => item_image_data_url(&db, id, path)?,
+            _ => None,
+        };
+        backup_items.push(BackupItem { item, image_base64 });
+    }
+    drop(db);
+
+    let backup = JsonBackup {
+        exported_at: now_timestamp(),
+        items: backup_items,
+    };
+
+    let writer = std::io::BufWriter::new(fs::File::create(&path)?);
+    serde_json::to_writer(writer, &backup).map_err(|e| AppError::Io(e.to_string()))?;
+
+    Ok(())
+}
+
+// Línea de export_ndjson; la imagen se indica por nombre de archivo en lugar
+// de incrustarse
+#[derive(Serialize)]
+struct NdjsonItem<'a> {
+    #[serde(flatten)]
+    item: &'a InventoryItem,
+    image_file: Option<String>,
+}
+
+// Un artículo por línea, escrito mientras se recorren las filas: la memoria no
+// crece con el tamaño del inventario
+#[tauri::command]
+fn export_ndjson(path: String, state: State<AppState>) -> Result<(), AppError> {
+    use std::io::Write;
+
+    let db = state.conn()?;
+    let mut stmt = db.prepare(&format!(
+        "SELECT {} FROM inventory WHERE deleted_at IS NULL ORDER BY created_at DESC, id DESC",
+        ITEM_COLUMNS
+    ))?;
+
+    let mut writer = std::io::BufWriter::new(fs::File::create(&path)?);
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let item = item_from_row(row)?;
+        let image_file = item
+            .image_path
+            .as_deref()
+            .filter(|path| *path != DB_IMAGE_MARKER)
+            .and_then(|path| Path::new(path).file_name())
+            .map(|name| name.to_string_lossy().to_string());
+
+        serde_json::to_writer(&mut writer, &NdjsonItem { item: &item, image_file })
+            .map_err(|e| AppError::Io(e.to_string()))?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+// Inserta los artículos del respaldo. Las rutas de imagen del archivo se
+// ignoran: cada imagen se vuelve a guardar en este equipo.
+fn import_backup_items(
+    db: &Connection,
+    items: &[BackupItem],
+    app_handle: &AppHandle,
+    now: &str,
+    saved_files: &mut Vec<String>,
+) -> Result<usize, AppError> {
+    for entry in items {
+        let item = &entry.item;
+
+        let saved = match &entry.image_base64 {
+            Some(data) => Some(save_image(data, app_handle)?),
+            None => None,
+        };
+        if let Some(saved) = &saved {
+            saved_files.extend(saved.files());
+        }
+
+        insert_backup_item(db, item, saved.as_ref(), now)?;
+    }
+
+    Ok(items.len())
+}
+
+// Parte de import_backup_items que solo toca la base de datos; la imagen llega
+// ya guardada
+fn insert_backup_item(
+    db: &Connection,
+    item: &InventoryItem,
+    saved: Option<&SavedImage>,
+    now: &str,
+) -> Result<i64, AppError> {
+    let name = validate_name(&item.name)?;
+    validate_quantities(item.cantidad_necesaria, item.cantidad_disponible)?;
+    validate_capacity(item.cantidad_disponible, item.capacidad_maxima)?;
+
+    // El proveedor puede no existir en este equipo
+    let supplier_id: Option<i64> = match item.supplier_id {
+        Some(id) => db
+            .query_row("SELECT id FROM suppliers WHERE id = ?1", [id], |row| row.get(0))
+            .optional()?,
+        None => None,
+    };
+
+    // Una fecha ilegible en el respaldo no debe desordenar el listado
+    let created_at = item
+        .created_at
+        .as_deref()
+        .and_then(parse_timestamp)
+        .unwrap_or_else(|| now.to_string());
+    let updated_at = item
+        .updated_at
+        .as_deref()
+        .and_then(parse_timestamp)
+        .unwrap_or_else(|| created_at.clone());
+    let category = normalize_optional_text(item.category.clone());
+    let descripcion = normalize_optional_text(item.descripcion.clone());
+    let codigo_barras = normalize_optional_text(item.codigo_barras.clone());
+    ensure_unique_barcode(db, codigo_barras.as_deref(), None)?;
+    let unidad = normalize_optional_text(item.unidad.clone());
+
+    db.execute(
+        "INSERT INTO inventory (name, image_path, cantidad_necesaria, cantidad_disponible, created_at, updated_at, category,
+             thumbnail_path, name_normalized, precio_unitario, costo_unitario, supplier_id, descripcion, codigo_barras, unidad,
+             punto_reorden, archived, capacidad_maxima, image_source_mime)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)",
+        params![
+            name,
+            saved.map(|s| s.image_path.clone()),
+            round_quantity(item.cantidad_necesaria),
+            round_quantity(item.cantidad_disponible),
+            created_at,
+            updated_at,
+            category,
+            saved.and_then(|s| s.thumbnail_path.clone()),
+            normalize_name(&name),
+            item.precio_unitario,
+            item.costo_unitario,
+            supplier_id,
+            descripcion,
+            codigo_barras,
+            unidad,
+            item.punto_reorden.map(round_quantity),
+            item.archived,
+            item.capacidad_maxima.map(round_quantity),
+            saved.map(|s| s.source_mime)
+        ],
+    )?;
+    let id = db.last_insert_rowid();
+    if let Some(saved) = saved {
+        store_image_blob(db, id, saved)?;
+    }
+    record_movement(
+        db,
+        id,
+        0.0,
+        round_quantity(item.cantidad_disponible),
+        Some("Importación JSON"),
+        now,
+    )?;
+
+    Ok(id)
+}
+
+#[tauri::command]
+fn import_json(path: String, state: State<AppState>) -> Result<usize, AppError> {
+    let file = fs::File::open(&path)?;
+    let backup: JsonBackup = serde_json::from_reader(std::io::BufReader::new(file))
+        .map_err(|e| AppError::InvalidInput(format!("El archivo no es un respaldo JSON válido: {}", e)))?;
+
+    let mut db = state.write_conn()?;
+
+    let now = now_timestamp();
+    let mut saved_files = Vec::new();
+
+    let imported = with_transaction(&mut db, |tx| {
+        import_backup_items(tx, &backup.items, &state.app_handle, &now, &mut saved_files)
+    });
+    match imported {
+        Ok(count) => Ok(count),
+        Err(e) => {
+            // La transacción ya se revirtió; solo quedan las imágenes escritas
+            remove_image_files(&saved_files);
+            Err(e)
+        }
+    }
+}
+
+#[tauri::command]
+fn backup_database(dest_path: String, state: State<AppState>) -> Result<i32, AppError> {
+    use rusqlite::backup::Backup;
+
+    let mut dest = Connection::open(&dest_path)?;
+    let db = state.conn()?;
+
+    // La API de respaldo de SQLite copia una instantánea consistente aunque
+    // la conexión siga abierta
+    let backup = Backup::new(&db, &mut dest)?;
+    backup.run_to_completion(100, std::time::Duration::from_millis(10), None)?;
+
+    Ok(backup.progress().pagecount)
+}
+
+#[tauri::command]
+fn restore_database(src_path: String, state: State<AppState>) -> Result<(), AppError> {
+    use std::io::Read;
+
+    let invalid = || AppError::InvalidInput("El archivo no es una base de datos SQLite válida".to_string());
+
+    // Validar la cabecera antes de tocar la base de datos actual
+    let mut header = [0u8; 16];
+    fs::File::open(&src_path)?
+        .read_exact(&mut header)
+        .map_err(|_| invalid())?;
+    if &header != SQLITE_HEADER {
+        return Err(invalid());
+    }
+
+    let has_inventory: bool = Connection::open_with_flags(&src_path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'inventory')",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|_| invalid())?;
+    if !has_inventory {
+        return Err(AppError::InvalidInput(
+            "El archivo no contiene un inventario".to_string(),
+        ));
+    }
+
+    // Con el turno de escritura tomado, la API de respaldo de SQLite copia el
+    // archivo dentro de la conexión abierta; las demás conexiones del pool ven
+    // la base anterior o la restaurada completa, nunca una mezcla
+    let mut db = state.write_conn()?;
+
+    // Copia de seguridad del inventario actual por si hay que volver atrás
+    let mut safety_path = get_app_data_dir(&state.app_handle)?;
+    safety_path.push("backups");
+    fs::create_dir_all(&safety_path)?;
+    safety_path.push(format!(
+        "inventario_{}.db",
+        chrono::Local::now().format("%Y%m%d_%H%M%S")
+    ));
+    restore_from_file(&mut db, Path::new(&src_path), &safety_path)?;
+
+    *state.settings.write()? = load_settings(&db)?;
+
+    Ok(())
+}
+
+// Guarda la base actual en safety_path y la reemplaza por src_path, ya
+// migrada. Si algo falla se vuelve a la copia de seguridad.
+fn restore_from_file(db: &mut Connection, src_path: &Path, safety_path: &Path) -> Result<(), AppError> {
+    db.backup(rusqlite::DatabaseName::Main, safety_path, None)?;
+
+    let restored = db
+        .restore(rusqlite::DatabaseName::Main, src_path, None::<fn(rusqlite::backup::Progress)>)
+        .and_then(|()| run_migrations(db));
+    if let Err(e) = restored {
+        db.restore(rusqlite::DatabaseName::Main, safety_path, None::<fn(rusqlite::backup::Progress)>)?;
+        return Err(e.into());
+    }
+
+    Ok(())
+}
+
+// VACUUM reescribe el archivo sin las páginas libres; con WAL el archivo
+// principal solo se reduce al hacer el checkpoint
+#[tauri::command]
+fn vacuum_database(state: State<AppState>) -> Result<(), AppError> {
+    let db = state.write_conn()?;
+
+    db.execute_batch("VACUUM")?;
+    db.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+
+    Ok(())
+}
+
+#[tauri::command]
+fn get_database_stats(state: State<AppState>) -> Result<DbStats, AppError> {
+    let db = state.conn()?;
+
+    let page_count: i64 = db.query_row("PRAGMA page_count", [], |row| row.get(0))?;
+    let item_count: i64 = db.query_row("SELECT COUNT(*) FROM inventory", [], |row| row.get(0))?;
+    drop(db);
+
+    // El archivo WAL también ocupa espacio mientras no se hace checkpoint
+    let db_path = get_db_file_path(&state.app_handle)?;
+    let file_size_bytes = ["", "-wal"]
+        .iter()
+        .filter_map(|suffix| {
+            let mut path = db_path.clone().into_os_string();
+            path.push(suffix);
+            fs::metadata(path).ok()
+        })
+        .map(|metadata| metadata.len())
+        .sum();
+
+    let images_dir = get_images_dir(&state.app_handle)?;
+    let mut image_count = 0;
+    let mut images_size_bytes = 0;
+
+    for dir in [images_dir.clone(), images_dir.join("thumbnails")] {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+
+        for metadata in entries.flatten().filter_map(|entry| entry.metadata().ok()) {
+            if metadata.is_file() {
+                image_count += 1;
+                images_size_bytes += metadata.len();
+            }
+        }
+    }
+
+    Ok(DbStats {
+        file_size_bytes,
+        page_count,
+        item_count,
+        image_count,
+        images_size_bytes,
+    })
+}
+
+#[tauri::command]
+fn check_integrity(state: State<AppState>) -> Result<IntegrityReport, AppError> {
+    let db = state.conn()?;
+
+    // integrity_check devuelve una sola fila "ok" cuando no hay problemas
+    let mut stmt = db.prepare("PRAGMA integrity_check")?;
+    let integrity_errors = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .filter(|message| message != "ok")
+        .collect::<Vec<_>>();
+    drop(stmt);
+
+    let mut stmt = db.prepare("PRAGMA foreign_key_check")?;
+    let foreign_key_errors = stmt
+        .query_map([], |row| {
+            let table: String = row.get(0)?;
+            let rowid: Option<i64> = row.get(1)?;
+            let parent: String = row.get(2)?;
+            Ok(format!(
+                "{} (fila {}) referencia a una fila inexistente de {}",
+                table,
+                rowid.map(|id| id.to_string()).unwrap_or_default(),
+                parent
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    drop(stmt);
+
+    let mut stmt = db.prepare(
+        "SELECT id, name, image_path, EXISTS(SELECT 1 FROM item_images WHERE item_id = inventory.id)
+         FROM inventory WHERE image_path IS NOT NULL ORDER BY id",
+    )?;
+    let missing_images = stmt
+        .query_map([], |row| {
+            Ok((
+                MissingImage {
+                    item_id: row.get(0)?,
+                    name: row.get(1)?,
+                    image_path: row.get(2)?,
+                },
+                row.get::<_, bool>(3)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .filter(|(missing, has_blob)| {
+            if missing.image_path == DB_IMAGE_MARKER {
+                !has_blob
+            } else {
+                !Path::new(&missing.image_path).exists()
+            }
+        })
+        .map(|(missing, _)| missing)
+        .collect();
+
+    Ok(IntegrityReport {
+        ok: integrity_errors.is_empty() && foreign_key_errors.is_empty(),
+        integrity_errors,
+        foreign_key_errors,
+        missing_images,
+    })
+}
+
+#[tauri::command]
+fn get_db_path(state: State<AppState>) -> Result<String, AppError> {
+    let db_path = get_db_file_path(&state.app_handle)?;
+
+    Ok(db_path.to_string_lossy().to_string())
+}
+
+// Rutas absolutas para los botones de "abrir carpeta"
+#[tauri::command]
+fn get_app_data_path(state: State<AppState>) -> Result<String, AppError> {
+    let data_dir = get_app_data_dir(&state.app_handle)?;
+
+    Ok(data_dir.to_string_lossy().to_string())
+}
+
+// La carpeta se crea si aún no hay imágenes, para que se pueda abrir
+#[tauri::command]
+fn get_images_path(state: State<AppState>) -> Result<String, AppError> {
+    let images_dir = get_images_dir(&state.app_handle)?;
+    fs::create_dir_all(&images_dir)?;
+
+    Ok(images_dir.to_string_lossy().to_string())
+}
+
+// Copia la base de datos a otra carpeta (por ejemplo una sincronizada) y la
+// usa desde ahí. El archivo anterior se conserva; las imágenes siguen en el
+// directorio de datos de la aplicación.
+#[tauri::command]
+fn set_database_path(new_path: String, state: State<AppState>) -> Result<(), AppError> {
+    let target_dir = PathBuf::from(new_path.trim());
+    if target_dir.as_os_str().is_empty() {
+        return Err(AppError::InvalidInput("La carpeta de destino no puede estar vacía".to_string()));
+    }
+    fs::create_dir_all(&target_dir)?;
+
+    // Comprobar que se puede escribir antes de cambiar nada
+    let probe = target_dir.join(".inventario_write_test");
+    fs::write(&probe, b"ok")
+        .and_then(|_| fs::remove_file(&probe))
+        .map_err(|e| AppError::Io(format!("No se puede escribir en {}: {}", target_dir.display(), e)))?;
+
+    let target = target_dir.join("inventario.db");
+    if target == get_db_file_path(&state.app_handle)? {
+        return Ok(());
+    }
+    if target.exists() {
+        return Err(AppError::InvalidInput(format!(
+            "Ya existe una base de datos en {}",
+            target.display()
+        )));
+    }
+
+    let writer = state.write_guard()?;
+    let mut pool = state.db.write()?;
+
+    // La API de respaldo incluye lo que aún está en el archivo WAL
+    pool.get()?.backup(rusqlite::DatabaseName::Main, &target, None)?;
+
+    let mut settings = load_local_settings(&state.app_handle)?;
+    settings.database_path = Some(target.to_string_lossy().to_string());
+
+    let switch = open_pool(&target).and_then(|new_pool| {
+        save_local_settings(&state.app_handle, &settings)?;
+        Ok(new_pool)
+    });
+
+    match switch {
+        Ok(new_pool) => {
+            *pool = new_pool;
+            drop(pool);
+            drop(writer);
+            restart_db_watcher(&state, &target);
+            Ok(())
+        }
+        Err(e) => {
+            for suffix in ["", "-wal", "-shm"] {
+                let mut file = target.clone().into_os_string();
+                file.push(suffix);
+                let _ = fs::remove_file(file);
+            }
+            Err(e)
+        }
+    }
+}
+
+#[tauri::command]
+fn fix_image_paths(state: State<AppState>) -> Result<i32, AppError> {
+    let db = state.write_conn()?;
+    let report = relocate_images(&db, &get_images_dir(&state.app_handle)?, None)?;
+    Ok(report.relocated as i32)
+}
+
+// Asigna en lote las fotos de una carpeta: cada archivo se asocia al artículo
+// cuyo nombre o código de barras (según match_by) coincide con el nombre del
+// archivo sin extensión, p. ej. "7501234567890.jpg"
+#[tauri::command]
+fn import_images_from_folder(
+    folder: String,
+    match_by: String,
+    state: State<AppState>,
+) -> Result<ImportImageReport, AppError> {
+    let query = match match_by.as_str() {
+        "name" => "SELECT id FROM inventory WHERE name_normalized = ?1 AND deleted_at IS NULL ORDER BY id LIMIT 1",
+        "barcode" => "SELECT id FROM inventory WHERE codigo_barras = ?1 AND deleted_at IS NULL",
+        _ => {
+            return Err(AppError::InvalidInput(format!(
+                "Criterio de coincidencia desconocido: \"{}\" (usa \"name\" o \"barcode\")",
+                match_by
+            )))
+        }
+    };
+
+    let entries = fs::read_dir(&folder)
+        .map_err(|e| AppError::Io(format!("No se pudo leer la carpeta {}: {}", folder, e)))?;
+    let mut files: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && has_image_extension(path))
+        .collect();
+    files.sort();
+
+    let mut report = ImportImageReport {
+        matched: 0,
+        unmatched: 0,
+        unmatched_files: Vec::new(),
+        failed_files: Vec::new(),
+    };
+
+    let mut db = state.write_conn()?;
+
+    for path in files {
+        let filename = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        let Some(stem) = path.file_stem().map(|stem| stem.to_string_lossy().trim().to_string()) else {
+            continue;
+        };
+        let key = match match_by.as_str() {
+            "name" => normalize_name(&stem),
+            _ => stem,
+        };
+
+        let Some(id) = db.query_row(query, [&key], |row| row.get::<_, i64>(0)).optional()? else {
+            report.unmatched += 1;
+            report.unmatched_files.push(filename);
+            continue;
+        };
+
+        let saved = match fs::read(&path)
+            .map_err(AppError::from)
+            .and_then(|data| save_image_bytes(data, None, &state.app_handle))
+        {
+            Ok(saved) => saved,
+            Err(e) => {
+                report.failed_files.push(format!("{}: {}", filename, e));
+                continue;
+            }
+        };
+
+        let now = now_timestamp();
+        let result = with_transaction(&mut db, |tx| {
+            let mut old_files = item_image_files(tx, id)?;
+            tx.execute(
+                "UPDATE inventory SET image_path = ?1, thumbnail_path = ?2, image_source_mime = ?3, updated_at = ?4,
+                     version = version + 1
+                 WHERE id = ?5",
+                params![saved.image_path, saved.thumbnail_path, saved.source_mime, now, id],
+            )?;
+            store_image_blob(tx, id, &saved)?;
+            old_files.retain(|path| *path != saved.image_path && Some(path) != saved.thumbnail_path.as_ref());
+            Ok(old_files)
+        });
+
+        match result {
+            Ok(old_files) => {
+                remove_image_files(&old_files);
+                emit_inventory_changed(&state.app_handle, id, "update");
+                report.matched += 1;
+            }
+            Err(e) => {
+                remove_image_files(&saved.files());
+                report.failed_files.push(format!("{}: {}", filename, e));
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+// Para no tomar como fotos otros archivos de la carpeta (Thumbs.db, etc.)
+fn has_image_extension(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+        .is_some_and(|ext| matches!(ext.as_str(), "png" | "jpg" | "jpeg" | "webp" | "gif"))
+}
+
+// Busca en old_dir, por nombre de archivo, las imágenes que ya no están en su
+// ruta registrada y las copia a la carpeta de imágenes actual. Sirve al
+// restaurar un respaldo hecho en otro equipo con otra estructura de carpetas.
+#[tauri::command]
+fn migrate_images(old_dir: String, state: State<AppState>) -> Result<ImageRelocationReport, AppError> {
+    let old_dir = PathBuf::from(old_dir);
+    if !old_dir.is_dir() {
+        return Err(AppError::InvalidInput(format!(
+            "La carpeta {} no existe",
+            old_dir.display()
+        )));
+    }
+
+    let db = state.write_conn()?;
+    relocate_images(&db, &get_images_dir(&state.app_handle)?, Some(&old_dir))
+}
+
+// Corrige las rutas de imágenes que no existen: primero busca el archivo en la
+// carpeta de imágenes actual y, si no está, lo copia desde search_dir
+fn relocate_images(
+    db: &Connection,
+    images_dir: &Path,
+    search_dir: Option<&Path>,
+) -> Result<ImageRelocationReport, AppError> {
+    let mut stmt = db.prepare("SELECT id, image_path FROM inventory WHERE image_path IS NOT NULL AND image_path != ?1")?;
+    let items: Vec<(i64, String)> = stmt
+        .query_map([DB_IMAGE_MARKER], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut report = ImageRelocationReport { relocated: 0, missing: 0 };
+
+    for (id, old_path) in items {
+        if Path::new(&old_path).exists() {
+            continue;
+        }
+
+        let Some(filename) = Path::new(&old_path).file_name() else {
+            report.missing += 1;
+            continue;
+        };
+
+        let new_path = images_dir.join(filename);
+        if !new_path.exists() {
+            match search_dir.map(|dir| dir.join(filename)).filter(|source| source.is_file()) {
+                Some(source) => {
+                    fs::create_dir_all(images_dir)?;
+                    fs::copy(&source, &new_path)?;
+                }
+                None => {
+                    report.missing += 1;
+                    continue;
+                }
+            }
+        }
+
+        db.execute(
+            "UPDATE inventory SET image_path = ?1 WHERE id = ?2",
+            params![new_path.to_string_lossy().to_string(), id],
+        )?;
+        report.relocated += 1;
+    }
+
+    Ok(report)
+}
+
+// Extensión según el tipo MIME declarado en el data URL
+fn extension_from_mime(mime: &str) -> Option<&'static str> {
+    match mime.trim().to_ascii_lowercase().as_str() {
+        "image/png" => Some("png"),
+        "image/jpeg" | "image/jpg" => Some("jpg"),
+        "image/webp" => Some("webp"),
+        "image/gif" => Some("gif"),
+        _ => None,
+    }
+}
+
+// Detectar el formato por los primeros bytes del archivo
+fn extension_from_bytes(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(b"\x89PNG") {
+        Some("png")
+    } else if data.starts_with(&[0xFF, 0xD8]) {
+        Some("jpg")
+    } else if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        Some("webp")
+    } else if data.starts_with(b"GIF8") {
+        Some("gif")
+    } else {
+        None
+    }
+}
+
+fn mime_for_extension(extension: &str) -> &'static str {
+    match extension {
+        "jpg" => "image/jpeg",
+        "webp" => "image/webp",
+        "gif" => "image/gif",
+        _ => "image/png",
+    }
+}
+
+struct ImageBlob {
+    mime: &'static str,
+    data: Vec<u8>,
+}
+
+struct SavedImage {
+    image_path: String,
+    thumbnail_path: Option<String>,
+    // Tipo recibido, antes de convertir a WebP o PNG
+    source_mime: &'static str,
+    // Presente cuando la imagen va a la tabla item_images
+    blob: Option<ImageBlob>,
+    // Archivos escritos ahora, para borrarlos si la operación se revierte; los
+    // reutilizados de otro artículo no se incluyen
+    created_files: Vec<String>,
+}
+
+impl SavedImage {
+    fn files(&self) -> Vec<String> {
+        self.created_files.clone()
+    }
+}
+
+// Guarda o quita el BLOB del artículo según dónde quedó su imagen nueva
+fn store_image_blob(db: &Connection, item_id: i64, saved: &SavedImage) -> Result<()> {
+    match &saved.blob {
+        Some(blob) => db.execute(
+            "INSERT OR REPLACE INTO item_images (item_id, mime, data) VALUES (?1, ?2, ?3)",
+            params![item_id, blob.mime, blob.data],
+        )?,
+        None => db.execute("DELETE FROM item_images WHERE item_id = ?1", [item_id])?,
+    };
+    Ok(())
+}
+
+// Miniatura PNG de máximo 200px en el lado largo; si la imagen no se puede
+// decodificar se devuelve None y se usa la imagen completa
+fn save_thumbnail(image_data: &[u8], images_dir: &std::path::Path, stem: &str) -> Option<String> {
+    let img = image::load_from_memory(image_data).ok()?;
+    let thumbnail = img.thumbnail(THUMBNAIL_MAX_SIZE, THUMBNAIL_MAX_SIZE);
+
+    let mut thumbnails_dir = images_dir.to_path_buf();
+    thumbnails_dir.push("thumbnails");
+    fs::create_dir_all(&thumbnails_dir).ok()?;
+
+    let mut thumbnail_path = thumbnails_dir;
+    thumbnail_path.push(format!("{}.png", stem));
+    thumbnail
+        .save_with_format(&thumbnail_path, image::ImageFormat::Png)
+        .ok()?;
+
+    Some(thumbnail_path.to_string_lossy().to_string())
+}
+
+// Reduce fotos con dimensiones excesivas; si algo falla se guarda la original
+fn downscale_image(image_data: Vec<u8>, extension: &str) -> Vec<u8> {
+    let format = match extension {
+        "png" => image::ImageFormat::Png,
+        "jpg" => image::ImageFormat::Jpeg,
+        _ => return image_data,
+    };
+
+    let img = match image::load_from_memory_with_format(&image_data, format) {
+        Ok(img) => img,
+        Err(_) => return image_data,
+    };
+    if img.width() <= MAX_IMAGE_DIMENSION && img.height() <= MAX_IMAGE_DIMENSION {
+        return image_data;
+    }
+
+    let resized = img.resize(
+        MAX_IMAGE_DIMENSION,
+        MAX_IMAGE_DIMENSION,
+        image::imageops::FilterType::Lanczos3,
+    );
+    let mut buffer = std::io::Cursor::new(Vec::new());
+    match resized.write_to(&mut buffer, format) {
+        Ok(()) => buffer.into_inner(),
+        Err(_) => image_data,
+    }
+}
+
+fn save_image(base64_data: &str, app_handle: &AppHandle) -> Result<SavedImage, AppError> {
+    let (mime, image_data) = decode_image_base64(base64_data, max_image_bytes(app_handle))?;
+    save_image_bytes(image_data, mime, app_handle)
+}
+
+// Devuelve el tipo declarado en el prefijo "data:", si lo hay, y los bytes
+fn decode_image_base64(base64_data: &str, max_bytes: usize) -> Result<(Option<&str>, Vec<u8>), AppError> {
+    use base64::{Engine as _, engine::general_purpose};
+
+    // Separar el prefijo "data:image/...;base64," si viene incluido
+    let (mime, encoded) = match base64_data.split_once("base64,") {
+        Some((prefix, data)) => (
+            prefix.strip_prefix("data:").and_then(|p| p.split(';').next()),
+            data,
+        ),
+        None => (None, base64_data),
+    };
+
+    // Acotar antes de revisar y decodificar: cada 3 bytes ocupan 4 caracteres
+    if encoded.len() > max_bytes.div_ceil(3) * 4 {
+        return Err(AppError::ImageTooLarge(max_bytes));
+    }
+    validate_base64(encoded)?;
+
+    Ok((mime, general_purpose::STANDARD.decode(encoded)?))
+}
+
+// Revisa la forma del texto sin decodificarlo: solo el alfabeto estándar, una
+// longitud múltiplo de 4 y como mucho dos "=" de relleno al final. Un texto
+// truncado se rechaza aquí con un mensaje claro.
+fn validate_base64(encoded: &str) -> Result<(), AppError> {
+    let bytes = encoded.as_bytes();
+    if bytes.is_empty() {
+        return Err(AppError::InvalidImageData("el texto está vacío".to_string()));
+    }
+    if !bytes.len().is_multiple_of(4) {
+        return Err(AppError::InvalidImageData(format!(
+            "la longitud ({}) no es múltiplo de 4; el texto puede estar incompleto",
+            bytes.len()
+        )));
+    }
+
+    let padding = bytes.iter().rev().take_while(|&&byte| byte == b'=').count();
+    if padding > 2 {
+        return Err(AppError::InvalidImageData("sobra relleno \"=\" al final".to_string()));
+    }
+    let body = &bytes[..bytes.len() - padding];
+    if let Some(position) = body
+        .iter()
+        .position(|&byte| !(byte.is_ascii_alphanumeric() || byte == b'+' || byte == b'/'))
+    {
+        return Err(AppError::InvalidImageData(format!(
+            "carácter no válido en la posición {}",
+            position
+        )));
+    }
+    Ok(())
+}
+
+// Tamaño máximo de una imagen decodificada según la preferencia max_image_bytes
+fn max_image_bytes(app_handle: &AppHandle) -> usize {
+    app_setting(app_handle, "max_image_bytes")
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(MAX_IMAGE_BYTES)
+}
+
+// mime es el tipo declarado por quien envía la imagen; sin él se detecta por
+// los primeros bytes
+fn save_image_bytes(image_data: Vec<u8>, mime: Option<&str>, app_handle: &AppHandle) -> Result<SavedImage, AppError> {
+    let max_bytes = max_image_bytes(app_handle);
+    if image_data.len() > max_bytes {
+        return Err(AppError::ImageTooLarge(max_bytes));
+    }
+
+    let extension = mime
+        .and_then(extension_from_mime)
+        .or_else(|| extension_from_bytes(&image_data))
+        .ok_or_else(|| AppError::ImageDecode("formato de imagen no soportado".to_string()))?;
+    let source_mime = mime_for_extension(extension);
+
+    // El nombre es el SHA-256 de la imagen recibida: la misma foto subida para
+    // varios artículos se guarda una sola vez
+    let stem = sha256_hex(&image_data);
+
+    let images_dir = get_images_dir(app_handle)?;
+    fs::create_dir_all(&images_dir)?;
+
+    // Con la conversión activada, GIF (el codificador de WebP no admite
+    // animación) se guarda como PNG
+    let webp_quality = webp_quality(app_handle).filter(|_| extension != "webp");
+    let target_extension = match webp_quality {
+        Some(_) if extension == "gif" => "png",
+        Some(_) => "webp",
+        None => extension,
+    };
+
+    let in_database = load_local_settings(app_handle)?.images_in_database;
+    let planned_exists = !in_database && images_dir.join(format!("{}.{}", stem, target_extension)).exists();
+
+    let (image_data, extension) = if planned_exists {
+        (image_data, target_extension)
+    } else {
+        let image_data = downscale_image(image_data, extension);
+        match webp_quality {
+            Some(quality) => transcode_image(image_data, extension, quality),
+            None => (image_data, extension),
+        }
+    };
+
+    // La conversión puede terminar en otro formato (PNG); se reutiliza el
+    // archivo final si ya existía, y uno que ya existía nunca va en
+    // created_files porque otros artículos pueden usarlo
+    let image_path = images_dir.join(format!("{}.{}", stem, extension));
+    let reuse_file = !in_database && image_path.exists();
+
+    let mut created_files = Vec::new();
+
+    let existing_thumbnail = images_dir.join("thumbnails").join(format!("{}.png", stem));
+    let thumbnail_path = if existing_thumbnail.exists() {
+        Some(existing_thumbnail.to_string_lossy().to_string())
+    } else {
+        let thumbnail_path = save_thumbnail(&image_data, &images_dir, &stem);
+        created_files.extend(thumbnail_path.clone());
+        thumbnail_path
+    };
+
+    if in_database {
+        return Ok(SavedImage {
+            image_path: DB_IMAGE_MARKER.to_string(),
+            thumbnail_path,
+            source_mime,
+            blob: Some(ImageBlob {
+                mime: mime_for_extension(extension),
+                data: image_data,
+            }),
+            created_files,
+        });
+    }
+
+    let image_path_str = image_path.to_string_lossy().to_string();
+    if !reuse_file {
+        fs::write(&image_path, &image_data)?;
+        created_files.push(image_path_str.clone());
+    }
+
+    Ok(SavedImage {
+        image_path: image_path_str,
+        thumbnail_path,
+        source_mime,
+        blob: None,
+        created_files,
+    })
+}
+
+// Calidad de WebP (1 a 100) si está activada la conversión
+fn webp_quality(app_handle: &AppHandle) -> Option<f32> {
+    if app_setting(app_handle, "image_format").as_deref() != Some("webp") {
+        return None;
+    }
+    app_setting(app_handle, "webp_quality")
+        .and_then(|value| value.parse::<u8>().ok())
+        .map(f32::from)
+}
+
+// WebP si se puede y, si no, PNG. Lo que ni siquiera se puede decodificar queda
+// como llegó.
+fn transcode_image(image_data: Vec<u8>, extension: &'static str, quality: f32) -> (Vec<u8>, &'static str) {
+    if extension != "gif" {
+        if let Some(webp_data) = encode_webp(&image_data, quality) {
+            return (webp_data, "webp");
+        }
+    }
+    if extension == "png" {
+        return (image_data, extension);
+    }
+    match encode_png(&image_data) {
+        Some(png_data) => (png_data, "png"),
+        None => (image_data, extension),
+    }
+}
+
+fn encode_png(image_data: &[u8]) -> Option<Vec<u8>> {
+    let img = image::load_from_memory(image_data).ok()?;
+    let mut encoded = std::io::Cursor::new(Vec::new());
+    img.write_to(&mut encoded, image::ImageFormat::Png).ok()?;
+    Some(encoded.into_inner())
+}
+
+fn encode_webp(image_data: &[u8], quality: f32) -> Option<Vec<u8>> {
+    let img = image::load_from_memory(image_data).ok()?;
+    let rgba = img.to_rgba8();
+    let encoded = webp::Encoder::from_rgba(&rgba, rgba.width(), rgba.height()).encode(quality);
+    Some(encoded.to_vec())
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+
+    Sha256::digest(data).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    let result = tauri::Builder::default()
+        .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_fs::init())
+        .plugin(tauri_plugin_dialog::init())
+        .setup(|app| {
+            let app_handle = app.handle().clone();
+            // Sin base de datos la aplicación no puede funcionar; se avisa con un
+            // diálogo en lugar de cerrarse sin explicación
+            let pool = match app_db_location(&app_handle).and_then(|location| init_database(&location)) {
+                Ok(pool) => pool,
+                Err(e) => {
+                    let exit_handle = app_handle.clone();
+                    app_handle
+                        .dialog()
+                        .message(format!("No se pudo iniciar el inventario.\n\n{}", e))
+                        .title("Error al iniciar")
+                        .kind(MessageDialogKind::Error)
+                        .show(move |_| exit_handle.exit(1));
+                    return Ok(());
+                }
+            };
+
+            // Sin preferencias guardadas se usan los valores por defecto
+            let settings = pool
+                .get()
+                .map_err(AppError::from)
+                .and_then(|conn| Ok(load_settings(&conn)?))
+                .unwrap_or_else(|e| {
+                    eprintln!("No se pudieron leer las preferencias: {}", e);
+                    HashMap::new()
+                });
+
+            app.manage(AppState {
+                db: RwLock::new(pool),
+                writer: Mutex::new(()),
+                last_deleted: Mutex::new(None),
+                last_write: Mutex::new(None),
+                db_watcher: Mutex::new(None),
+                settings: RwLock::new(settings),
+                low_stock_scheduler: Mutex::new(Some(tauri::async_runtime::spawn(run_low_stock_scheduler(
+                    app_handle.clone(),
+                )))),
+                app_handle: app_handle.clone(),
+            });
+
+            if let Ok(db_path) = get_db_file_path(&app_handle) {
+                restart_db_watcher(&app.state::<AppState>(), &db_path);
+            }
+
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            get_all_items,
+            get_archived_items,
+            set_archived,
+            get_item,
+            get_items_by_ids,
+            count_items,
+            get_items_paginated,
+            get_recent_items,
+            get_items_created_between,
+            get_items_sorted,
+            get_items_filtered,
+            search_items,
+            search_fts,
+            rebuild_search_index,
+            get_item_by_barcode,
+            find_duplicates,
+            add_item,
+            add_items,
+            update_item,
+            adjust_quantity,
+            set_needed_quantities,
+            consume_stock,
+            begin_physical_count,
+            apply_count,
+            finish_count,
+            get_item_history,
+            get_movements,
+            format_timestamp,
+            clear_item_image,
+            cleanup_orphaned_images,
+            repair_image_links,
+            list_images,
+            get_items_without_images,
+            get_items_with_images,
+            get_thumbnail,
+            get_image,
+            set_image_storage,
+            get_image_storage,
+            get_setting,
+            set_setting,
+            migrate_images_to_database,
+            delete_item,
+            hard_delete_item,
+            restore_item,
+            undo_last_delete,
+            get_deleted_items,
+            bulk_delete,
+            merge_items,
+            duplicate_item,
+            get_low_stock_items,
+            get_out_of_stock_items,
+            get_items_by_stock_ratio,
+            get_reorder_report,
+            get_variance_report,
+            get_top_shortfall,
+            get_statistics,
+            get_dashboard_data,
+            get_inventory_value,
+            get_categories,
+            get_items_grouped,
+            add_supplier,
+            get_suppliers,
+            update_supplier,
+            delete_supplier,
+            get_items_by_supplier,
+            add_tag_to_item,
+            remove_tag_from_item,
+            get_tags,
+            get_items_by_tag,
+            delete_tag,
+            export_csv,
+            export_csv_filtered,
+            import_csv,
+            export_pdf,
+            export_xlsx,
+            export_json,
+            export_ndjson,
+            import_json,
+            backup_database,
+            restore_database,
+            get_db_path,
+            get_app_data_path,
+            get_images_path,
+            set_database_path,
+            check_integrity,
+            vacuum_database,
+            get_database_stats,
+            fix_image_paths,
+            migrate_images,
+            check_storage_access,
+            import_images_from_folder
+        ])
+        .build(tauri::generate_context!());
+
+    // Los errores al abrir la base de datos ya se muestran en un diálogo desde
+    // setup; aquí llegan los de Tauri, cuando ya no hay ventana donde mostrarlos
+    let app = match result {
+        Ok(app) => app,
+        Err(e) => {
+            eprintln!("Error al ejecutar la aplicación: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    app.run(|app_handle, event| {
+        // La revisión programada no debe empezar otra consulta mientras se cierra
+        if let tauri::RunEvent::Exit = event {
+            if let Some(state) = app_handle.try_state::<AppState>() {
+                if let Some(task) = state.low_stock_scheduler.lock().ok().and_then(|mut task| task.take()) {
+                    task.abort();
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Base con solo las primeras `version` migraciones, como la de una
+    // instalación anterior
+    fn database_at_version(version: usize) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        for migration in &MIGRATIONS[..version] {
+            migration(&conn).unwrap();
+        }
+        conn.pragma_update(None, "user_version", version as i64).unwrap();
+        conn
+    }
+
+    fn memory_db() -> DbPool {
+        init_database(&DbLocation::Memory).unwrap()
+    }
+
+    fn new_item(name: &str, cantidad_necesaria: f64, cantidad_disponible: f64) -> NewItem {
+        NewItem {
+            name: name.to_string(),
+            image_base64: None,
+            cantidad_necesaria,
+            cantidad_disponible,
+            category: None,
+            precio_unitario: None,
+            costo_unitario: None,
+            supplier_id: None,
+            descripcion: None,
+            codigo_barras: None,
+            unidad: None,
+            punto_reorden: None,
+            capacidad_maxima: None,
+            created_at: None,
+            reason: None,
+            allow_duplicate: false,
+        }
+    }
+
+    // Base de datos en un archivo propio de la prueba, para abrir varias conexiones
+    fn temp_db_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("inventario-{}-{}.db", name, std::process::id()));
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+        path
+    }
+
+    fn add(db: &Connection, name: &str, cantidad_disponible: f64) -> i64 {
+        create_item(db, &new_item(name, 10.0, cantidad_disponible), None, &now_timestamp()).unwrap()
+    }
+
+    #[test]
+    fn memory_pool_applies_all_migrations() {
+        let pool = memory_db();
+        let db = pool.get().unwrap();
+        let applied: i64 = db.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap();
+        assert_eq!(applied, MIGRATIONS.len() as i64);
+    }
+
+    #[test]
+    fn adjust_quantity_records_movement() {
+        let pool = memory_db();
+        let mut db = pool.get().unwrap();
+        let id = add(&db, "Tornillo", 5.0);
+
+        let (item, previous) =
+            with_transaction(&mut db, |tx| apply_quantity_delta(tx, id, -2.0, Some("Venta"), &now_timestamp())).unwrap();
+        assert_eq!(previous, 5.0);
+        assert_eq!(item.cantidad_disponible, 3.0);
+
+        let delta: f64 = db
+            .query_row("SELECT delta FROM stock_movements WHERE item_id = ?1 AND reason = 'Venta'", [id], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(delta, -2.0);
+    }
+
+    #[test]
+    fn adjust_quantity_below_zero_changes_nothing() {
+        let pool = memory_db();
+        let mut db = pool.get().unwrap();
+        let id = add(&db, "Tornillo", 1.0);
+
+        let result = with_transaction(&mut db, |tx| apply_quantity_delta(tx, id, -2.0, None, &now_timestamp()));
+        assert!(matches!(result, Err(AppError::InvalidInput(_))));
+        assert_eq!(fetch_item(&db, id).unwrap().cantidad_disponible, 1.0);
+    }
+
+    #[test]
+    fn adjust_quantity_of_trashed_item_is_not_found() {
+        let pool = memory_db();
+        let mut db = pool.get().unwrap();
+        let id = add(&db, "Tornillo", 1.0);
+        soft_delete_item(&db, id, &now_timestamp()).unwrap();
+
+        let result = with_transaction(&mut db, |tx| apply_quantity_delta(tx, id, 1.0, None, &now_timestamp()));
+        assert!(matches!(result, Err(AppError::NotFound(found)) if found == id));
+    }
+
+    #[test]
+    fn bulk_delete_sends_to_trash() {
+        let pool = memory_db();
+        let db = pool.get().unwrap();
+        let first = add(&db, "Tornillo", 1.0);
+        let second = add(&db, "Tuerca", 1.0);
+
+        let deleted = soft_delete_items(&db, &[first, second, first, 999], &now_timestamp()).unwrap();
+        assert_eq!(deleted, vec![first, second]);
+
+        let trashed: i64 = db
+            .query_row("SELECT COUNT(*) FROM inventory WHERE deleted_at IS NOT NULL", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(trashed, 2);
+        assert!(matches!(fetch_item(&db, first), Err(AppError::NotFound(_))));
+    }
+
+    #[test]
+    fn duplicate_numbers_the_copies() {
+        let pool = memory_db();
+        let db = pool.get().unwrap();
+        let id = add(&db, "Tornillo", 4.0);
+
+        let mut created_files = Vec::new();
+        let first = copy_item(&db, id, &now_timestamp(), &mut created_files).unwrap();
+        let second = copy_item(&db, id, &now_timestamp(), &mut created_files).unwrap();
+
+        let first = fetch_item(&db, first).unwrap();
+        assert_eq!(first.name, "Tornillo (copia)");
+        assert_eq!(first.cantidad_disponible, 0.0);
+        assert_eq!(fetch_item(&db, second).unwrap().name, "Tornillo (copia 2)");
+        assert!(created_files.is_empty());
+    }
+
+    #[test]
+    fn merge_adds_quantities_and_removes_the_other() {
+        let pool = memory_db();
+        let db = pool.get().unwrap();
+        let keep = add(&db, "Tornillo", 4.0);
+        let remove = add(&db, "Tornillos", 2.5);
+
+        let (item, _) = merge_item_rows(&db, keep, remove, None, &now_timestamp()).unwrap();
+        assert_eq!(item.cantidad_disponible, 6.5);
+        assert!(matches!(fetch_item(&db, remove), Err(AppError::NotFound(_))));
+        assert!(matches!(
+            merge_item_rows(&db, keep, keep, None, &now_timestamp()),
+            Err(AppError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn import_csv_reports_bad_rows() {
+        let pool = memory_db();
+        let db = pool.get().unwrap();
+        let data = "name,cantidad_necesaria,cantidad_disponible\nTornillo,10,4\n,1,1\nTuerca,x,1\n";
+        let mut reader = csv::Reader::from_reader(data.as_bytes());
+
+        let report = import_csv_rows(&db, &mut reader, false, &now_timestamp()).unwrap();
+        assert_eq!(report.inserted, 1);
+        assert_eq!(report.skipped, 2);
+        assert_eq!(report.errors.iter().map(|e| e.row).collect::<Vec<_>>(), vec![3, 4]);
+    }
+
+    #[test]
+    fn add_item_retry_while_another_connection_writes() {
+        let path = temp_db_path("add-retry");
+        open_database(&path).unwrap();
+
+        let locked = std::sync::Arc::new(std::sync::Barrier::new(2));
+        let holder = {
+            let path = path.clone();
+            let locked = locked.clone();
+            std::thread::spawn(move || {
+                let conn = Connection::open(&path).unwrap();
+                conn.execute_batch("BEGIN IMMEDIATE").unwrap();
+                locked.wait();
+                std::thread::sleep(Duration::from_millis(300));
+                conn.execute_batch("COMMIT").unwrap();
+            })
+        };
+
+        let mut conn = Connection::open(&path).unwrap();
+        conn.busy_timeout(Duration::from_millis(10)).unwrap();
+        locked.wait();
+
+        let item = new_item("Tornillo", 10.0, 4.0);
+        let mut attempts = 0;
+        let id = retry_on_busy(|| {
+            attempts += 1;
+            with_transaction(&mut conn, |tx| create_item(tx, &item, None, &now_timestamp()))
+        })
+        .unwrap();
+        holder.join().unwrap();
+
+        assert!(attempts > 1);
+        let rows: i64 = conn.query_row("SELECT COUNT(*) FROM inventory", [], |row| row.get(0)).unwrap();
+        let movements: i64 = conn
+            .query_row("SELECT COUNT(*) FROM stock_movements WHERE item_id = ?1", [id], |row| row.get(0))
+            .unwrap();
+        assert_eq!((rows, movements), (1, 1));
+    }
+
+    #[test]
+    fn concurrent_reads_share_the_pool() {
+        let path = temp_db_path("concurrent-reads");
+        let pool = init_database(&DbLocation::File(path)).unwrap();
+        {
+            let db = pool.get().unwrap();
+            for index in 0..20 {
+                add(&db, &format!("Artículo {}", index), 1.0);
+            }
+        }
+
+        // Una escritura abierta no debe bloquear las lecturas (WAL)
+        let mut writer = pool.get().unwrap();
+        let tx = writer.transaction().unwrap();
+        add(&tx, "Pendiente", 1.0);
+
+        let readers: Vec<_> = (0..DB_POOL_SIZE * 2)
+            .map(|_| {
+                let pool = pool.clone();
+                std::thread::spawn(move || {
+                    let db = pool.get().unwrap();
+                    query_all_items(&db, false).unwrap().len()
+                })
+            })
+            .collect();
+        let counts: Vec<usize> = readers.into_iter().map(|reader| reader.join().unwrap()).collect();
+
+        tx.commit().unwrap();
+        assert!(counts.iter().all(|count| *count == 20));
+    }
+
+    #[test]
+    fn imports_reject_overlong_names() {
+        let pool = memory_db();
+        let db = pool.get().unwrap();
+        let long_name = "a".repeat(10 * 1024);
+
+        let data = format!("name,cantidad_disponible\n{},1\nTornillo,1\n", long_name);
+        let mut reader = csv::Reader::from_reader(data.as_bytes());
+        let report = import_csv_rows(&db, &mut reader, false, &now_timestamp()).unwrap();
+        assert_eq!((report.inserted, report.skipped), (1, 1));
+        assert_eq!(report.errors[0].row, 2);
+
+        let mut item = fetch_item(&db, add(&db, "Tuerca", 1.0)).unwrap();
+        item.name = long_name;
+        let result = insert_backup_item(&db, &item, None, &now_timestamp());
+        assert!(matches!(result, Err(AppError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn imports_reject_negative_quantities() {
+        let pool = memory_db();
+        let db = pool.get().unwrap();
+
+        let data = "name,cantidad_necesaria,cantidad_disponible\nTornillo,0,0\nTuerca,-1,0\nArandela,0,-1\n";
+        let mut reader = csv::Reader::from_reader(data.as_bytes());
+        let report = import_csv_rows(&db, &mut reader, false, &now_timestamp()).unwrap();
+        assert_eq!((report.inserted, report.skipped), (1, 2));
+        assert_eq!(report.errors.iter().map(|e| e.row).collect::<Vec<_>>(), vec![3, 4]);
+
+        let mut item = fetch_item(&db, add(&db, "Clavo", 1.0)).unwrap();
+        item.name = "Clavo de respaldo".to_string();
+        item.cantidad_necesaria = 0.0;
+        item.cantidad_disponible = 0.0;
+        assert!(insert_backup_item(&db, &item, None, &now_timestamp()).is_ok());
+
+        item.name = "Clavo negativo".to_string();
+        item.cantidad_disponible = -1.0;
+        let result = insert_backup_item(&db, &item, None, &now_timestamp());
+        assert!(matches!(result, Err(AppError::InvalidInput(_))));
+
+        item.cantidad_disponible = 5.0;
+        item.capacidad_maxima = Some(4.0);
+        let result = insert_backup_item(&db, &item, None, &now_timestamp());
+        assert!(matches!(result, Err(AppError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn validate_name_trims_and_rejects_blank() {
+        assert!(matches!(validate_name(""), Err(AppError::InvalidInput(_))));
+        assert!(matches!(validate_name("   "), Err(AppError::InvalidInput(_))));
+        assert_eq!(validate_name("  real name  ").unwrap(), "real name");
+    }
+
+    #[test]
+    fn truncated_base64_is_rejected() {
+        assert!(matches!(validate_base64("iVBORw0KGgo"), Err(AppError::InvalidImageData(_))));
+        assert!(matches!(validate_base64("iVBO===="), Err(AppError::InvalidImageData(_))));
+        assert!(matches!(validate_base64("iVB*"), Err(AppError::InvalidImageData(_))));
+        assert!(matches!(
+            decode_image_base64("data:image/png;base64,iVBORw0KGgo", MAX_IMAGE_BYTES),
+            Err(AppError::InvalidImageData(_))
+        ));
+
+        let (mime, data) = decode_image_base64("data:image/png;base64,iVBORw0KGgo=", MAX_IMAGE_BYTES).unwrap();
+        assert_eq!(mime, Some("image/png"));
+        assert_eq!(data, b"\x89PNG\r\n\x1a\n");
+    }
+
+    #[test]
+    fn oversized_base64_is_rejected_before_decoding() {
+        let encoded = "A".repeat(16);
+        assert!(decode_image_base64(&encoded, 12).is_ok());
+        assert!(matches!(decode_image_base64(&encoded, 9), Err(AppError::ImageTooLarge(9))));
+    }
+
+    #[test]
+    fn timestamps_keep_their_order_after_the_utc_rewrite() {
+        let now = now_timestamp();
+        assert_eq!(parse_timestamp(&now).as_deref(), Some(now.as_str()));
+
+        let pool = memory_db();
+        let db = pool.get().unwrap();
+        // Fechas locales sin zona de versiones anteriores, lejos del cambio de horario
+        let legacy = ["2020-01-15 08:00:00", "2020-01-15 23:30:00", "2021-07-01 12:00:00"];
+        for (index, created_at) in legacy.iter().enumerate() {
+            let id = add(&db, &format!("Antiguo {}", index), 1.0);
+            db.execute("UPDATE inventory SET created_at = ?1 WHERE id = ?2", params![created_at, id])
+                .unwrap();
+        }
+        migration_utc_timestamps(&db).unwrap();
+        let latest = add(&db, "Nuevo", 1.0);
+
+        let rows: Vec<(i64, String)> = db
+            .prepare("SELECT id, created_at FROM inventory ORDER BY created_at")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let names: Vec<String> = rows.iter().map(|(id, _)| fetch_item(&db, *id).unwrap().name).collect();
+        assert_eq!(names, ["Antiguo 0", "Antiguo 1", "Antiguo 2", "Nuevo"]);
+        assert_eq!(rows.last().unwrap().0, latest);
+        for (_, created_at) in &rows {
+            assert_eq!(parse_timestamp(created_at).as_deref(), Some(created_at.as_str()));
+        }
+    }
+
+    fn query_plan(db: &Connection, sql: &str) -> String {
+        let mut stmt = db.prepare(&format!("EXPLAIN QUERY PLAN {}", sql)).unwrap();
+        let details = stmt
+            .query_map([], |row| row.get::<_, String>(3))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        details.join("\n")
+    }
+
+    #[test]
+    fn name_and_date_queries_use_their_indexes() {
+        let pool = memory_db();
+        let db = pool.get().unwrap();
+
+        // Las mismas formas que get_all_items, ensure_unique_name y los listados por nombre
+        let by_date = query_plan(
+            &db,
+            "SELECT id FROM inventory WHERE deleted_at IS NULL AND (1 OR archived = 0) ORDER BY created_at DESC, id DESC",
+        );
+        assert!(by_date.contains("USING INDEX idx_inventory_created_at"), "{}", by_date);
+
+        let by_name = query_plan(&db, "SELECT id FROM inventory WHERE name = 'Tornillo' COLLATE NOCASE");
+        assert!(by_name.contains("USING COVERING INDEX idx_inventory_name"), "{}", by_name);
+
+        let sorted = query_plan(&db, "SELECT id FROM inventory WHERE deleted_at IS NULL ORDER BY name COLLATE NOCASE, id");
+        assert!(sorted.contains("USING INDEX idx_inventory_name"), "{}", sorted);
+    }
+
+    fn encoded(format: image::ImageFormat) -> Vec<u8> {
+        let mut data = std::io::Cursor::new(Vec::new());
+        image::DynamicImage::new_rgb8(4, 4).write_to(&mut data, format).unwrap();
+        data.into_inner()
+    }
+
+    #[test]
+    fn transcoding_falls_back_to_png() {
+        let (data, extension) = transcode_image(encoded(image::ImageFormat::Png), "png", 80.0);
+        assert_eq!((extension_from_bytes(&data), extension), (Some("webp"), "webp"));
+
+        let (data, extension) = transcode_image(encoded(image::ImageFormat::Gif), "gif", 80.0);
+        assert_eq!((extension_from_bytes(&data), extension), (Some("png"), "png"));
+
+        // Lo que no se puede decodificar queda como llegó
+        let (data, extension) = transcode_image(b"GIF89a roto".to_vec(), "gif", 80.0);
+        assert_eq!((data.as_slice(), extension), (&b"GIF89a roto"[..], "gif"));
+    }
+
+    #[test]
+    fn source_mime_is_kept_with_the_item() {
+        let pool = memory_db();
+        let db = pool.get().unwrap();
+        let saved = SavedImage {
+            image_path: DB_IMAGE_MARKER.to_string(),
+            thumbnail_path: None,
+            source_mime: "image/png",
+            blob: Some(ImageBlob { mime: "image/webp", data: vec![1, 2, 3] }),
+            created_files: Vec::new(),
+        };
+
+        let id = create_item(&db, &new_item("Tornillo", 1.0, 1.0), Some(&saved), &now_timestamp()).unwrap();
+        assert_eq!(fetch_item(&db, id).unwrap().image_source_mime.as_deref(), Some("image/png"));
+        let stored: String = db
+            .query_row("SELECT mime FROM item_images WHERE item_id = ?1", [id], |row| row.get(0))
+            .unwrap();
+        assert_eq!(stored, "image/webp");
+    }
+
+    #[test]
+    fn restore_replaces_the_database_under_open_readers() {
+        let source_path = temp_db_path("restore-source");
+        {
+            let source = open_database(&source_path).unwrap();
+            add(&source, "Tornillo", 1.0);
+            add(&source, "Tuerca", 1.0);
+        }
+
+        let pool = init_database(&DbLocation::File(temp_db_path("restore-live"))).unwrap();
+        let reader = pool.get().unwrap();
+        add(&reader, "Clavo", 1.0);
+
+        let mut writer = pool.get().unwrap();
+        restore_from_file(&mut writer, &source_path, &temp_db_path("restore-safety")).unwrap();
+
+        let names: Vec<String> = query_all_items(&reader, true).unwrap().into_iter().map(|item| item.name).collect();
+        assert_eq!(names.len(), 2);
+        assert!(!names.contains(&"Clavo".to_string()));
+    }
+
+    #[test]
+    fn restore_only_trashed_items_with_a_free_name() {
+        let pool = memory_db();
+        let db = pool.get().unwrap();
+        let id = add(&db, "Tornillo", 1.0);
+        assert!(matches!(restore_trashed_item(&db, id), Err(AppError::NotFound(_))));
+
+        soft_delete_item(&db, id, &now_timestamp()).unwrap();
+        let other = add(&db, "tornillo", 1.0);
+        assert!(matches!(restore_trashed_item(&db, id), Err(AppError::DuplicateName(_))));
+
+        delete_item_row(&db, other).unwrap();
+        restore_trashed_item(&db, id).unwrap();
+        assert_eq!(fetch_item(&db, id).unwrap().name, "Tornillo");
+        assert!(matches!(delete_item_row(&db, other), Err(AppError::NotFound(_))));
+    }
+
+    #[test]
+    fn created_at_default_is_rebuilt_as_utc() {
+        // Una base que se quedó en la migración anterior, con el valor por
+        // defecto en hora local y un artículo eliminado al final
+        let mut conn = database_at_version(24);
+        conn.execute("INSERT INTO inventory (name, updated_at) VALUES ('Tornillo', 'x')", []).unwrap();
+        conn.execute("INSERT INTO inventory (name, updated_at) VALUES ('Tuerca', 'x')", []).unwrap();
+        conn.execute("DELETE FROM inventory WHERE name = 'Tuerca'", []).unwrap();
+
+        run_migrations(&mut conn).unwrap();
+
+        let created_at: String =
+            conn.query_row("SELECT created_at FROM inventory WHERE name = 'Tornillo'", [], |row| row.get(0)).unwrap();
+        assert_eq!(parse_timestamp(&created_at).as_deref(), Some(created_at.as_str()));
+        assert!(created_at.ends_with('Z'));
+
+        let id = add(&conn, "Clavo", 1.0);
+        assert_eq!(id, 3);
+        conn.execute("INSERT INTO inventory (name) VALUES ('Arandela')", []).unwrap();
+        let created_at: String =
+            conn.query_row("SELECT created_at FROM inventory WHERE name = 'Arandela'", [], |row| row.get(0)).unwrap();
+        assert!(created_at.ends_with('Z'));
+
+        // Los triggers de la búsqueda se conservan
+        let found: i64 = conn
+            .query_row("SELECT COUNT(*) FROM inventory_fts WHERE inventory_fts MATCH 'clavo'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(found, 1);
+    }
+
+    #[test]
+    fn quantity_columns_are_rebuilt_as_real() {
+        let mut conn = database_at_version(25);
+        conn.execute(
+            "INSERT INTO inventory (name, cantidad_necesaria, cantidad_disponible) VALUES ('Aceite', 4, 2)",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO stock_movements (item_id, delta, previous_qty, new_qty, timestamp) VALUES (1, 2, 0, 2, 'x')",
+            [],
+        )
+        .unwrap();
+
+        run_migrations(&mut conn).unwrap();
+
+        for (table, columns) in [
+            ("inventory", &["cantidad_necesaria", "cantidad_disponible", "punto_reorden", "capacidad_maxima"][..]),
+            ("stock_movements", &["delta", "previous_qty", "new_qty"][..]),
+            ("count_lines", &["snapshot_qty", "counted_qty", "final_qty", "variance"][..]),
+        ] {
+            for column in columns {
+                let declared: String = conn
+                    .query_row(
+                        "SELECT type FROM pragma_table_info(?1) WHERE name = ?2",
+                        params![table, column],
+                        |row| row.get(0),
+                    )
+                    .unwrap();
+                assert_eq!(declared, "REAL", "{}.{}", table, column);
+            }
+        }
+
+        let stored: String = conn
+            .query_row("SELECT typeof(cantidad_disponible) FROM inventory WHERE id = 1", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(stored, "real");
+        let movements: i64 = conn.query_row("SELECT COUNT(*) FROM stock_movements", [], |row| row.get(0)).unwrap();
+        assert_eq!(movements, 1);
+    }
+
+    #[test]
+    fn update_bumps_the_version_and_rejects_stale_edits() {
+        let pool = memory_db();
+        let mut db = pool.get().unwrap();
+        let id = add(&db, "Tornillo", 5.0);
+
+        let mut edit = new_item("Tornillo largo", 10.0, 7.0);
+        let outcome = with_transaction(&mut db, |tx| apply_item_update(tx, id, &edit, Some(1), None)).unwrap();
+        assert_eq!(outcome.previous_qty, 5.0);
+        let item = fetch_item(&db, id).unwrap();
+        assert_eq!(item.version, 2);
+        assert_eq!(item.name, "Tornillo largo");
+        assert_eq!(item.cantidad_disponible, 7.0);
+
+        // Quien todavía tiene la versión 1 no sobrescribe el cambio
+        edit.cantidad_disponible = 1.0;
+        let err = with_transaction(&mut db, |tx| apply_item_update(tx, id, &edit, Some(1), None)).unwrap_err();
+        assert_eq!(err.code(), "VERSION_CONFLICT");
+        let item = fetch_item(&db, id).unwrap();
+        assert_eq!(item.version, 2);
+        assert_eq!(item.cantidad_disponible, 7.0);
+
+        // Sin versión esperada se sobrescribe
+        with_transaction(&mut db, |tx| apply_item_update(tx, id, &edit, None, None)).unwrap();
+        assert_eq!(fetch_item(&db, id).unwrap().version, 3);
+    }
+
+    #[test]
+    fn update_keeps_names_unique() {
+        let pool = memory_db();
+        let mut db = pool.get().unwrap();
+        add(&db, "Tornillo", 5.0);
+        let id = add(&db, "Tuerca", 5.0);
+
+        let err = with_transaction(&mut db, |tx| apply_item_update(tx, id, &new_item("tornillo", 10.0, 5.0), None, None))
+            .unwrap_err();
+        assert_eq!(err.code(), "ITEM_NAME_EXISTS");
+        assert_eq!(fetch_item(&db, id).unwrap().version, 1);
+
+        // Conservar el propio nombre no es un duplicado
+        with_transaction(&mut db, |tx| apply_item_update(tx, id, &new_item("TUERCA", 10.0, 5.0), None, None)).unwrap();
+
+        let mut duplicate = new_item("Tornillo", 10.0, 5.0);
+        duplicate.allow_duplicate = true;
+        with_transaction(&mut db, |tx| apply_item_update(tx, id, &duplicate, None, None)).unwrap();
+        assert_eq!(fetch_item(&db, id).unwrap().name, "Tornillo");
+    }
+
+    #[test]
+    fn search_ignores_accents_and_wildcards() {
+        let pool = memory_db();
+        let db = pool.get().unwrap();
+        let solucion = add(&db, "Solución salina", 5.0);
+        let descuento = add(&db, "Descuento 50%", 5.0);
+        add(&db, "Descuento 500", 5.0);
+        let trashed = add(&db, "Solución vieja", 5.0);
+        db.execute("UPDATE inventory SET deleted_at = ?1 WHERE id = ?2", params![now_timestamp(), trashed]).unwrap();
+
+        let ids = |query: &str| search_like(&db, query).unwrap().iter().map(|item| item.id).collect::<Vec<_>>();
+        assert_eq!(ids("SOLUCION"), vec![Some(solucion)]);
+        assert_eq!(ids("50%"), vec![Some(descuento)]);
+        assert!(ids("_").is_empty());
+
+        // El texto del usuario va como parámetro, nunca dentro de la consulta
+        let filter = ItemFilter {
+            query: Some("' OR 1=1 --".to_string()),
+            sort_by: Some("name; DROP TABLE inventory".to_string()),
+            ..Default::default()
+        };
+        let (sql, values) = build_item_query(&filter);
+        assert!(!sql.contains("OR 1=1") && !sql.contains("DROP"));
+        assert_eq!(values.len(), 1);
+        assert!(query_items(&db, &filter).unwrap().is_empty());
+    }
+
+    #[test]
+    fn archived_items_leave_the_default_listings() {
+        let pool = memory_db();
+        let db = pool.get().unwrap();
+        let id = add(&db, "Tornillo", 5.0);
+        add(&db, "Tuerca", 5.0);
+
+        let item = update_archived(&db, id, true).unwrap();
+        assert!(item.archived);
+        assert_eq!(item.version, 2);
+        assert_eq!(query_all_items(&db, false).unwrap().len(), 1);
+        assert_eq!(query_all_items(&db, true).unwrap().len(), 2);
+        assert_eq!(query_archived_items(&db).unwrap().iter().map(|item| item.id).collect::<Vec<_>>(), vec![Some(id)]);
+
+        update_archived(&db, id, false).unwrap();
+        assert!(query_archived_items(&db).unwrap().is_empty());
+        assert_eq!(update_archived(&db, 99, true).unwrap_err().code(), "NOT_FOUND");
+    }
+
+    #[test]
+    fn pdf_report_is_written() {
+        let pool = memory_db();
+        let db = pool.get().unwrap();
+        for index in 0..60 {
+            add(&db, &format!("Artículo {}", index), index as f64 / 4.0);
+        }
+
+        let path = std::env::temp_dir().join(format!("inventario-reporte-{}.pdf", std::process::id()));
+        write_pdf_report(&db, LOW_STOCK_DEFAULT_PERCENT, &path).unwrap();
+        let written = fs::read(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert!(written.starts_with(b"%PDF"));
+    }
+
+    #[test]
+    fn create_validates_quantities() {
+        let pool = memory_db();
+        let db = pool.get().unwrap();
+        let now = now_timestamp();
+
+        let id = create_item(&db, &new_item("Vacío", 0.0, 0.0), None, &now).unwrap();
+        assert_eq!(fetch_item(&db, id).unwrap().cantidad_disponible, 0.0);
+
+        for (name, necesaria, disponible) in [("Faltante", -1.0, 0.0), ("Deuda", 0.0, -1.0), ("Roto", f64::NAN, 0.0)] {
+            let err = create_item(&db, &new_item(name, necesaria, disponible), None, &now).unwrap_err();
+            assert_eq!(err.code(), "INVALID_INPUT", "{}", name);
+        }
+        let count: i64 = db.query_row("SELECT COUNT(*) FROM inventory", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 1);
+
+        // Los decimales se aceptan y se redondean a QUANTITY_DECIMALS
+        let id = create_item(&db, &new_item("Aceite", 2.5, 1.23456), None, &now).unwrap();
+        let item = fetch_item(&db, id).unwrap();
+        assert_eq!(item.cantidad_necesaria, 2.5);
+        assert_eq!(item.cantidad_disponible, 1.235);
+    }
+
+    #[test]
+    fn update_validates_quantities() {
+        let pool = memory_db();
+        let mut db = pool.get().unwrap();
+        let id = add(&db, "Aceite", 5.0);
+
+        for (necesaria, disponible) in [(-1.0, 5.0), (10.0, -1.0), (10.0, f64::INFINITY)] {
+            let edit = new_item("Aceite", necesaria, disponible);
+            let err = with_transaction(&mut db, |tx| apply_item_update(tx, id, &edit, None, None)).unwrap_err();
+            assert_eq!(err.code(), "INVALID_INPUT");
+        }
+        let item = fetch_item(&db, id).unwrap();
+        assert_eq!((item.cantidad_disponible, item.version), (5.0, 1));
+
+        let edit = new_item("Aceite", 0.0, 0.0);
+        with_transaction(&mut db, |tx| apply_item_update(tx, id, &edit, None, None)).unwrap();
+        assert_eq!(fetch_item(&db, id).unwrap().cantidad_disponible, 0.0);
+
+        let edit = new_item("Aceite", 0.75, 0.3333);
+        with_transaction(&mut db, |tx| apply_item_update(tx, id, &edit, None, None)).unwrap();
+        let item = fetch_item(&db, id).unwrap();
+        assert_eq!((item.cantidad_necesaria, item.cantidad_disponible), (0.75, 0.333));
+    }
+
+    #[test]
+    fn import_csv_creates_items_like_the_form() {
+        let pool = memory_db();
+        let db = pool.get().unwrap();
+        add(&db, "Tornillo", 5.0);
+        let data = "name,cantidad_disponible,category,created_at\n\
+                    Tuerca,4,  ,2024-03-01T10:00:00Z\n\
+                    tornillo,1,,\n\
+                    Arandela,1,,ayer\n";
+        let mut reader = csv::Reader::from_reader(data.as_bytes());
+
+        let report = import_csv_rows(&db, &mut reader, false, &now_timestamp()).unwrap();
+        assert_eq!(report.inserted, 1);
+        assert_eq!(report.errors.iter().map(|e| e.row).collect::<Vec<_>>(), vec![3, 4]);
+
+        let (id, created_at, category): (i64, String, Option<String>) = db
+            .query_row("SELECT id, created_at, category FROM inventory WHERE name = 'Tuerca'", [], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .unwrap();
+        assert_eq!(created_at, "2024-03-01T10:00:00Z");
+        assert_eq!(category, None);
+        let reason: String = db
+            .query_row("SELECT reason FROM stock_movements WHERE item_id = ?1", [id], |row| row.get(0))
+            .unwrap();
+        assert_eq!(reason, "Importación CSV");
+    }
+
+    #[test]
+    fn clearing_the_image_skips_trashed_items() {
+        let pool = memory_db();
+        let db = pool.get().unwrap();
+        let id = add(&db, "Tornillo", 5.0);
+        db.execute("UPDATE inventory SET image_path = '/tmp/no-existe.webp' WHERE id = ?1", [id]).unwrap();
+
+        let files = clear_image_row(&db, id).unwrap();
+        assert_eq!(files, vec!["/tmp/no-existe.webp".to_string()]);
+        let item = fetch_item(&db, id).unwrap();
+        assert_eq!((item.image_path, item.version), (None, 2));
+
+        db.execute("UPDATE inventory SET deleted_at = ?1 WHERE id = ?2", params![now_timestamp(), id]).unwrap();
+        assert_eq!(clear_image_row(&db, id).unwrap_err().code(), "NOT_FOUND");
+        assert_eq!(clear_image_row(&db, 99).unwrap_err().code(), "NOT_FOUND");
+    }
+}
@@ -31,22 +31,24 @@ export class InventoryService {
     return await this.invoke<InventoryItem[]>('get_all_items');
   }
 
-  async addItem(name: string, cantidadNecesaria: number, cantidadDisponible: number, imageBase64?: string): Promise<InventoryItem> {
+  async addItem(name: string, cantidadNecesaria: number, cantidadDisponible: number, imageBase64?: string, allowDuplicate = false): Promise<InventoryItem> {
     return await this.invoke<InventoryItem>('add_item', {
       name,
       imageBase64,
       cantidadNecesaria,
-      cantidadDisponible
+      cantidadDisponible,
+      allowDuplicate
     });
   }
 
-  async updateItem(id: number, name: string, cantidadNecesaria: number, cantidadDisponible: number, imageBase64?: string): Promise<InventoryItem> {
+  async updateItem(id: number, name: string, cantidadNecesaria: number, cantidadDisponible: number, imageBase64?: string, allowDuplicate = false): Promise<InventoryItem> {
     return await this.invoke<InventoryItem>('update_item', {
       id,
       name,
       imageBase64,
       cantidadNecesaria,
-      cantidadDisponible
+      cantidadDisponible,
+      allowDuplicate
     });
   }
 
@@ -76,15 +78,11 @@ async exportDatabase() {
       throw new Error('Exportación cancelada');
     }
 
-    // 2. Obtener la ruta de la base de datos desde Rust
-    console.log('C. Obteniendo ruta de DB...');
-    const dbPath = await this.invoke<string>('get_db_path');
-    console.log('D. Ruta DB:', dbPath);
-
-    // 3. Copiar el archivo .db
-    console.log('E. Copiando archivo DB...');
-    await copyFile(dbPath, exportPath);
-    console.log('F. Archivo DB copiado exitosamente');
+    // 2-3. Respaldar la base de datos desde Rust (en modo WAL copiar el
+    // archivo directamente puede omitir los últimos cambios)
+    console.log('C. Respaldando base de datos...');
+    const pages = await this.invoke<number>('backup_database', { destPath: exportPath });
+    console.log('F. Base de datos respaldada exitosamente, páginas:', pages);
 
     // 4. Crear carpeta de imágenes EN LA MISMA ubicación que el .db exportado
     const exportFolder = exportPath.substring(0, exportPath.lastIndexOf('\\'));
@@ -147,11 +145,8 @@ async importDatabase() {
 
     console.log('B. Archivo seleccionado:', selectedFile);
 
-    const currentDbPath = await this.invoke<string>('get_db_path');
-    console.log('C. Ruta DB actual:', currentDbPath);
-
-    console.log('D. Sobrescribiendo base de datos...');
-    await copyFile(selectedFile as string, currentDbPath);
+    console.log('D. Restaurando base de datos...');
+    await this.invoke<void>('restore_database', { srcPath: selectedFile as string });
     console.log('E. Base de datos importada exitosamente');
 
     const pathSeparator = (selectedFile as string).includes('\\') ? '\\' : '/';